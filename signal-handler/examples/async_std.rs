@@ -0,0 +1,64 @@
+/*
+RUST_BACKTRACE=1 RUST_LOG=trace cargo run -p signal-handler --example async_std --features impl_async_std --
+*/
+
+//
+#[cfg(feature = "impl_async_std")]
+#[async_std::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use core::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use std::{process, sync::Arc, time::Instant};
+
+    //
+    let ctx = Arc::new(AtomicUsize::new(1));
+    let instant = Instant::now();
+
+    let handler = signal_handler::Handler::builder()
+        .initialized(|info| {
+            println!("initialized info:{:?}", info);
+
+            let pid = process::id();
+            println!("kill -HUP {}", pid);
+            println!("kill -USR1 {}", pid);
+            println!("kill -TERM {}", pid);
+            println!("Control-C");
+        })
+        .reload_config_async({
+            let ctx = ctx.clone();
+            move |info| {
+                let ctx = ctx.clone();
+
+                Box::pin(async move {
+                    async_std::task::sleep(Duration::from_secs(3)).await;
+
+                    ctx.fetch_add(1, Ordering::SeqCst);
+                    println!("reload_config_async info:{:?}", info);
+                })
+            }
+        })
+        .print_stats(move |info| {
+            println!(
+                "print_stats info:{:?} ctx:{:?} uptime:{:?}",
+                info,
+                ctx,
+                instant.elapsed(),
+            );
+        })
+        .wait_for_stop(|info| {
+            println!("wait_for_stop info:{:?}", info);
+        })
+        .build();
+
+    let outcome = handler.handle_async_with_async_std().await?;
+    println!("outcome:{:?} exit_code:{}", outcome, outcome.exit_code());
+
+    Ok(())
+}
+
+#[cfg(not(feature = "impl_async_std"))]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    Ok(())
+}
@@ -74,18 +74,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let pid = process::id();
             println!("kill -HUP {}", pid);
             println!("kill -USR1 {}", pid);
+            println!("kill -USR2 {}", pid);
             println!("kill -TERM {}", pid);
             println!("Control-C");
         })
-        .reload_config({
+        .try_reload_config({
             let ctx = ctx.clone();
             move |info| {
                 thread::sleep(Duration::from_secs(3));
 
-                ctx.fetch_add(1, Ordering::SeqCst);
+                if ctx.fetch_add(1, Ordering::SeqCst) > 3 {
+                    return Err(IoError::other("config invalid"));
+                }
                 println!("reload_config info:{:?}", info);
+
+                Ok(())
             }
         })
+        .on_error(|tp, err| {
+            println!("on_error tp:{:?} err:{}", tp, err);
+        })
         .print_stats({
             move |info| {
                 println!(
@@ -96,6 +104,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 );
             }
         })
+        .on_signal("usr2", [signal_handler::SIGUSR2], |info| {
+            println!("on_signal usr2 info:{:?}", info);
+        })
         .wait_for_stop(|info| {
             // e.g.
             // rocksdb::DBWithThreadMode::flush_wal
@@ -104,7 +115,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         })
         .build();
 
-    handler.handle()?;
+    let outcome = handler.handle()?;
+    println!("outcome:{:?} exit_code:{}", outcome, outcome.exit_code());
 
     //
     drop(tcp_accept_join_handle);
@@ -9,38 +9,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     use core::sync::atomic::{AtomicUsize, Ordering};
     use std::{io::Error as IoError, process, sync::Arc, time::Instant};
 
+    use signal_handler::handler::StopStep;
     use tokio::{net::TcpListener, sync::mpsc, task::JoinHandle};
 
-    let port = portpicker::pick_unused_port().expect("No ports free");
-    let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
-
+    //
     let (tcp_accept_tx, mut tcp_accept_rx) = mpsc::unbounded_channel();
-    let tcp_accept_join_handle: JoinHandle<Result<(), IoError>> = tokio::spawn(async move {
-        tcp_accept_rx.recv().await.unwrap();
-
-        println!("telnet 127.0.0.1 {}", port);
 
-        loop {
-            let (mut stream, addr) = listener.accept().await?;
-            println!("new client, addr:{:?}", addr);
-            tokio::spawn(async move {
-                let (mut r, mut w) = stream.split();
-                match tokio::io::copy(&mut r, &mut w).await {
-                    Ok(n) => {
-                        println!("copy succeeded, addr:{:?} n:{}", addr, n);
-                    }
-                    Err(err) => {
-                        println!("copy failed, addr:{:?} err:{}", addr, err);
-                    }
-                }
-            });
-        }
-
-        #[allow(unreachable_code)]
-        Ok(())
-    });
-
-    //
     let ctx = Arc::new(AtomicUsize::new(1));
     let instant = Instant::now();
 
@@ -89,13 +63,52 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             println!("wait_for_stop info:{:?}", info);
         })
+        .stop_steps([StopStep::Graceful, StopStep::Force])
+        .on_stop_escalation(|_, next| {
+            if next == Some(StopStep::Force) {
+                println!("Control-C again to force exit");
+            }
+        })
         .build();
 
+    //
+    let port = portpicker::pick_unused_port().expect("No ports free");
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
+
+    let shutdown_token = handler.shutdown_token();
+    let tcp_accept_join_handle: JoinHandle<Result<(), IoError>> = tokio::spawn(async move {
+        tcp_accept_rx.recv().await.unwrap();
+
+        println!("telnet 127.0.0.1 {}", port);
+
+        loop {
+            let (mut stream, addr) = tokio::select! {
+                ret = listener.accept() => ret?,
+                _ = shutdown_token.cancelled() => break,
+            };
+            println!("new client, addr:{:?}", addr);
+            tokio::spawn(async move {
+                let (mut r, mut w) = stream.split();
+                match tokio::io::copy(&mut r, &mut w).await {
+                    Ok(n) => {
+                        println!("copy succeeded, addr:{:?} n:{}", addr, n);
+                    }
+                    Err(err) => {
+                        println!("copy failed, addr:{:?} err:{}", addr, err);
+                    }
+                }
+            });
+        }
+
+        println!("tcp accept stopped");
+
+        Ok(())
+    });
+
     handler.handle_async_with_tokio().await?;
 
     //
-    tcp_accept_join_handle.abort();
-    assert!(tcp_accept_join_handle.await.unwrap_err().is_cancelled());
+    tcp_accept_join_handle.await??;
 
     Ok(())
 }
@@ -2,20 +2,52 @@ use core::{
     future::Future,
     ops::{Deref, DerefMut},
     pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use crate::{
+    handler::stats::HandlerStats,
+    register::{RegisterEvent, RegisterType, SignalNumber, SignalOrigin},
 };
-use std::{collections::HashMap, sync::Arc, time::SystemTime};
 
 //
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub struct CallbackInfo {
+    // Wall clock, for logging only.
     pub time: SystemTime,
+    // Monotonic, unaffected by clock changes.
+    pub instant: Instant,
+    // Increases with every info created in the process.
+    pub sequence: u64,
+    // The number of further signals folded into this invocation.
+    pub coalesced: usize,
+    pub deadline: Option<Instant>,
+    pub signal_number: Option<SignalNumber>,
+    pub origin: Option<SignalOrigin>,
+    // Set for the print_stats callbacks only.
+    pub stats: Option<Arc<HandlerStats>>,
 }
 
 impl Default for CallbackInfo {
     fn default() -> Self {
+        static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
         Self {
             time: SystemTime::now(),
+            instant: Instant::now(),
+            sequence: NEXT_SEQUENCE.fetch_add(1, Ordering::SeqCst),
+            coalesced: 0,
+            deadline: None,
+            signal_number: None,
+            origin: None,
+            stats: None,
         }
     }
 }
@@ -29,19 +61,68 @@ impl CallbackInfo {
         &self.time
     }
 
+    pub fn instant(&self) -> &Instant {
+        &self.instant
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn coalesced(&self) -> usize {
+        self.coalesced
+    }
+
+    pub fn with_deadline(deadline: Option<Instant>) -> Self {
+        Self {
+            deadline,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_event(event: &RegisterEvent) -> Self {
+        Self {
+            signal_number: event.signal_number,
+            origin: event.origin,
+            ..Self::default()
+        }
+    }
+
+    pub fn deadline(&self) -> Option<&Instant> {
+        self.deadline.as_ref()
+    }
+
+    pub fn signal_number(&self) -> Option<SignalNumber> {
+        self.signal_number
+    }
+
+    pub fn origin(&self) -> Option<&SignalOrigin> {
+        self.origin.as_ref()
+    }
+
+    pub fn stats(&self) -> Option<&HandlerStats> {
+        self.stats.as_deref()
+    }
+
     pub fn time_now() -> SystemTime {
         SystemTime::now()
     }
 }
 
+//
+pub type CallbackError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
 //
 #[derive(Clone)]
 pub enum Callback {
-    Sync(Arc<dyn Fn(CallbackInfo) + Send + Sync + 'static>),
+    Sync(Arc<dyn Fn(CallbackInfo) -> Result<(), CallbackError> + Send + Sync + 'static>),
     #[allow(clippy::type_complexity)]
     Async(
         Arc<
-            dyn Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>
+            dyn Fn(
+                    CallbackInfo,
+                )
+                    -> Pin<Box<dyn Future<Output = Result<(), CallbackError>> + Send + 'static>>
                 + Send
                 + Sync
                 + 'static,
@@ -63,7 +144,10 @@ impl Callback {
     where
         F: Fn(CallbackInfo) + Send + Sync + 'static,
     {
-        Self::Sync(Arc::new(cb))
+        Self::Sync(Arc::new(move |info| {
+            cb(info);
+            Ok(())
+        }))
     }
 
     pub fn with_async<F>(cb: F) -> Self
@@ -73,25 +157,233 @@ impl Callback {
             + Sync
             + 'static,
     {
-        Self::Async(Arc::new(cb))
+        Self::Async(Arc::new(move |info| {
+            let fut = cb(info);
+            Box::pin(async move {
+                fut.await;
+                Ok(())
+            })
+        }))
+    }
+
+    pub fn with_try_sync<F, E>(cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        Self::Sync(Arc::new(move |info| cb(info).map_err(Into::into)))
+    }
+
+    pub fn with_try_async<F, E>(cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = Result<(), E>> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+        E: Into<CallbackError> + 'static,
+    {
+        Self::Async(Arc::new(move |info| {
+            let fut = cb(info);
+            Box::pin(async move { fut.await.map_err(Into::into) })
+        }))
     }
 }
 
 //
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum CallbackType {
     Initialized,
     ReloadConfig,
     WaitForStop,
+    FastStop,
     PrintStats,
+    Custom(&'static str),
+}
+
+impl From<RegisterType> for CallbackType {
+    fn from(tp: RegisterType) -> Self {
+        match tp {
+            #[cfg(not(windows))]
+            RegisterType::ReloadConfig => Self::ReloadConfig,
+            RegisterType::WaitForStop => Self::WaitForStop,
+            #[cfg(not(windows))]
+            RegisterType::PrintStats => Self::PrintStats,
+            RegisterType::Custom(name) => Self::Custom(name),
+        }
+    }
+}
+
+impl CallbackType {
+    pub fn register_type(&self) -> Option<RegisterType> {
+        match self {
+            Self::Initialized => None,
+            #[cfg(not(windows))]
+            Self::ReloadConfig => Some(RegisterType::ReloadConfig),
+            #[cfg(windows)]
+            Self::ReloadConfig => None,
+            Self::WaitForStop => Some(RegisterType::WaitForStop),
+            Self::FastStop => None,
+            #[cfg(not(windows))]
+            Self::PrintStats => Some(RegisterType::PrintStats),
+            #[cfg(windows)]
+            Self::PrintStats => None,
+            Self::Custom(name) => Some(RegisterType::Custom(name)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Initialized => "initialized",
+            Self::ReloadConfig => "reload_config",
+            Self::WaitForStop => "wait_for_stop",
+            Self::FastStop => "fast_stop",
+            Self::PrintStats => "print_stats",
+            Self::Custom(name) => name,
+        }
+    }
+
+    // Stop listeners run in reverse registration order, like destructors.
+    pub fn is_reverse_order(&self) -> bool {
+        matches!(self, Self::WaitForStop | Self::FastStop)
+    }
+}
+
+//
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ExecutionMode {
+    #[default]
+    Sequential,
+    Concurrent,
+}
+
+//
+// How signals of a type received close together are turned into invocations.
+// The default drops the signals received while running, as the handler always did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CoalescePolicy {
+    // Signals received while running are folded into one follow-up invocation.
+    CoalesceWhileRunning,
+    // Every signal is an invocation, run one after another.
+    Queue,
+    // Signals received while running are dropped.
+    #[default]
+    DropWhileRunning,
+    // Runs once no further signal was received for the duration.
+    Debounce(Duration),
+    // Runs at most once per duration, the signals in between are folded into the next invocation.
+    Throttle(Duration),
+}
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+impl ListenerId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+//
+#[derive(Debug, Clone)]
+pub struct Listener {
+    pub id: ListenerId,
+    pub callback: Callback,
+    pub priority: i32,
+}
+
+// Ordered by priority (higher first), then by registration order.
+#[derive(Debug, Clone, Default)]
+pub struct Listeners {
+    list: Vec<Listener>,
+    mode: ExecutionMode,
+    coalesce: CoalescePolicy,
+}
+
+impl Listeners {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, listener: Listener, reverse_order: bool) {
+        let index = self
+            .list
+            .iter()
+            .position(|x| {
+                if reverse_order {
+                    x.priority <= listener.priority
+                } else {
+                    x.priority < listener.priority
+                }
+            })
+            .unwrap_or(self.list.len());
+        self.list.insert(index, listener);
+    }
+
+    pub fn remove(&mut self, id: ListenerId) -> bool {
+        let len = self.list.len();
+        self.list.retain(|x| x.id != id);
+        self.list.len() != len
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Listener> {
+        self.list.iter()
+    }
+
+    pub fn callbacks(&self) -> impl Iterator<Item = &Callback> {
+        self.list.iter().map(|x| &x.callback)
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn mode(&self) -> ExecutionMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ExecutionMode) {
+        self.mode = mode;
+    }
+
+    pub fn coalesce(&self) -> CoalescePolicy {
+        self.coalesce
+    }
+
+    pub fn set_coalesce(&mut self, coalesce: CoalescePolicy) {
+        self.coalesce = coalesce;
+    }
+
+    pub fn has_async(&self) -> bool {
+        self.callbacks().any(|cb| matches!(cb, Callback::Async(_)))
+    }
+
+    // Sequentially, concurrent listeners are spawned by the handler.
+    // A failed callback does not prevent the others from running.
+    pub(crate) fn call_sync(&self, info: CallbackInfo) -> Vec<CallbackError> {
+        self.callbacks()
+            .filter_map(|cb| match cb {
+                Callback::Sync(cb) => cb(info.clone()).err(),
+                Callback::Async(_) => unreachable!(),
+            })
+            .collect()
+    }
 }
 
 //
 #[derive(Debug, Clone, Default)]
-pub struct Callbacks(HashMap<CallbackType, Callback>);
+pub struct Callbacks(HashMap<CallbackType, Listeners>);
 
 impl Deref for Callbacks {
-    type Target = HashMap<CallbackType, Callback>;
+    type Target = HashMap<CallbackType, Listeners>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -109,11 +401,111 @@ impl Callbacks {
         Self::default()
     }
 
-    pub fn into_inner(self) -> HashMap<CallbackType, Callback> {
+    pub fn into_inner(self) -> HashMap<CallbackType, Listeners> {
         self.0
     }
 
+    pub fn add(&mut self, tp: CallbackType, callback: Callback, priority: i32) -> ListenerId {
+        let id = ListenerId::next();
+        self.entry(tp).or_default().insert(
+            Listener {
+                id,
+                callback,
+                priority,
+            },
+            tp.is_reverse_order(),
+        );
+        id
+    }
+
+    pub fn remove(&mut self, id: ListenerId) -> bool {
+        self.values_mut().any(|listeners| listeners.remove(id))
+    }
+
+    // `None` if there is no listener for the type.
+    pub fn listeners(&self, tp: &CallbackType) -> Option<Listeners> {
+        self.get(tp).filter(|x| !x.is_empty()).cloned()
+    }
+
+    pub fn set_mode(&mut self, tp: CallbackType, mode: ExecutionMode) {
+        self.entry(tp).or_default().set_mode(mode);
+    }
+
+    // Also used without listeners, by HandlerStream.
+    pub fn coalesce(&self, tp: &CallbackType) -> CoalescePolicy {
+        self.get(tp).map(|x| x.coalesce()).unwrap_or_default()
+    }
+
+    pub fn set_coalesce(&mut self, tp: CallbackType, coalesce: CoalescePolicy) {
+        self.entry(tp).or_default().set_coalesce(coalesce);
+    }
+
     pub fn has_async(&self) -> bool {
-        self.iter().any(|(_, cb)| matches!(cb, Callback::Async(_)))
+        self.values().any(|listeners| listeners.has_async())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(callbacks: &Callbacks, tp: CallbackType) -> Vec<ListenerId> {
+        callbacks
+            .listeners(&tp)
+            .unwrap()
+            .iter()
+            .map(|x| x.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_listeners_order() {
+        let mut callbacks = Callbacks::new();
+
+        let tp = CallbackType::ReloadConfig;
+        let a = callbacks.add(tp, Callback::with_sync(|_| {}), 0);
+        let b = callbacks.add(tp, Callback::with_sync(|_| {}), 10);
+        let c = callbacks.add(tp, Callback::with_sync(|_| {}), 0);
+        let d = callbacks.add(tp, Callback::with_sync(|_| {}), -10);
+        let e = callbacks.add(tp, Callback::with_sync(|_| {}), 10);
+
+        // By priority, then in registration order.
+        assert_eq!(ids(&callbacks, tp), vec![b, e, a, c, d]);
+
+        assert!(callbacks.remove(a));
+        assert!(!callbacks.remove(a));
+        assert_eq!(ids(&callbacks, tp), vec![b, e, c, d]);
+    }
+
+    #[test]
+    fn test_listeners_reverse_order() {
+        let mut callbacks = Callbacks::new();
+
+        for tp in [CallbackType::WaitForStop, CallbackType::FastStop] {
+            assert!(tp.is_reverse_order());
+
+            let a = callbacks.add(tp, Callback::with_sync(|_| {}), 0);
+            let b = callbacks.add(tp, Callback::with_sync(|_| {}), 10);
+            let c = callbacks.add(tp, Callback::with_sync(|_| {}), 0);
+            let d = callbacks.add(tp, Callback::with_sync(|_| {}), -10);
+            let e = callbacks.add(tp, Callback::with_sync(|_| {}), 10);
+
+            // By priority, then in reverse registration order.
+            assert_eq!(ids(&callbacks, tp), vec![e, b, c, a, d]);
+        }
+    }
+
+    #[test]
+    fn test_try_async() {
+        // Any error convertible into a CallbackError, like with_try_sync.
+        let cb =
+            Callback::with_try_async(|_| Box::pin(async { Err(std::io::Error::other("failed")) }));
+
+        let err = match cb {
+            Callback::Async(cb) => futures_executor::block_on(cb(CallbackInfo::default())),
+            Callback::Sync(_) => unreachable!(),
+        }
+        .unwrap_err();
+        assert_eq!(err.to_string(), "failed");
     }
 }
@@ -1,9 +1,27 @@
-use core::{future::Future, pin::Pin};
+use core::{any::Any, future::Future, pin::Pin};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
+#[cfg(feature = "systemd")]
+use crate::systemd::{Notifier, Watchdog};
 use crate::{
-    callback::{Callback, CallbackInfo, CallbackType, Callbacks},
-    handler::Handler,
-    register::Registers,
+    callback::{
+        Callback, CallbackError, CallbackInfo, CallbackType, Callbacks, CoalescePolicy,
+        ExecutionMode,
+    },
+    handler::{
+        error::ErrorHandling,
+        panic::{PanicHandling, PanicPolicy},
+        stop::{StopLadder, StopStep},
+        Handler,
+    },
+    register::{RegisterType, Registers, SignalNumber},
+    reload::ConfigReloader,
+    stats::{MetricsRegistry, StatsFormat, StatsPrinter},
 };
 
 //
@@ -12,6 +30,18 @@ use crate::{
 pub struct Builder {
     pub callbacks: Callbacks,
     pub registers: Registers,
+    pub stop_ladder: StopLadder,
+    pub stop_timeout: Option<Duration>,
+    pub stop_timeout_exit_code: Option<i32>,
+    pub restore_default_on_exit: bool,
+    pub panic_handling: PanicHandling,
+    pub error_handling: ErrorHandling,
+    #[cfg(target_os = "linux")]
+    pub watch_paths: Vec<PathBuf>,
+    #[cfg(feature = "systemd")]
+    pub notifier: Option<Notifier>,
+    #[cfg(feature = "systemd")]
+    pub watchdog: Option<Watchdog>,
 }
 
 impl Builder {
@@ -23,13 +53,101 @@ impl Builder {
         Handler::from_builder(self)
     }
 
+    //
+    pub fn signals<I>(mut self, tp: RegisterType, signal_numbers: I) -> Self
+    where
+        I: IntoIterator<Item = SignalNumber>,
+    {
+        self.registers.set_signal_numbers(tp, signal_numbers);
+
+        self
+    }
+
+    pub fn extend_signals<I>(mut self, tp: RegisterType, signal_numbers: I) -> Self
+    where
+        I: IntoIterator<Item = SignalNumber>,
+    {
+        self.registers.extend_signal_numbers(tp, signal_numbers);
+
+        self
+    }
+
+    pub fn restore_default_on_exit(mut self, restore_default_on_exit: bool) -> Self {
+        self.restore_default_on_exit = restore_default_on_exit;
+
+        self
+    }
+
+    // e.g. `Notifier::from_env()?`, None disables the notifications.
+    #[cfg(feature = "systemd")]
+    pub fn sd_notify(mut self, notifier: Option<Notifier>) -> Self {
+        self.notifier = notifier;
+
+        self
+    }
+
+    // e.g. `Watchdog::from_env()`, pings are sent only with a notifier.
+    #[cfg(feature = "systemd")]
+    pub fn sd_watchdog(mut self, watchdog: Option<Watchdog>) -> Self {
+        self.watchdog = watchdog;
+
+        self
+    }
+
+    //
+    pub fn callback(mut self, tp: CallbackType, priority: i32, cb: Callback) -> Self {
+        self.callbacks.add(tp, cb, priority);
+
+        if let Some(register_tp) = tp.register_type() {
+            self.registers.get_or_insert_default(register_tp);
+        }
+
+        self
+    }
+
+    pub fn execution_mode(mut self, tp: CallbackType, mode: ExecutionMode) -> Self {
+        self.callbacks.set_mode(tp, mode);
+
+        self
+    }
+
+    pub fn coalesce(mut self, tp: CallbackType, coalesce: CoalescePolicy) -> Self {
+        self.callbacks.set_coalesce(tp, coalesce);
+
+        self
+    }
+
+    pub fn panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.panic_handling.set_policy(policy);
+
+        self
+    }
+
+    pub fn on_error<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(CallbackType, &CallbackError) + Send + Sync + 'static,
+    {
+        self.error_handling.set_hook(Arc::new(hook));
+
+        self
+    }
+
+    pub fn on_callback_panic<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(CallbackType, &(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.panic_handling.set_hook(Arc::new(hook));
+
+        self
+    }
+
     //
     pub fn initialized<F>(mut self, cb: F) -> Self
     where
         F: Fn(CallbackInfo) + Send + Sync + 'static,
     {
         let cb = Callback::with_sync(cb);
-        self.callbacks.insert(CallbackType::Initialized, cb);
+        self.callbacks.add(CallbackType::Initialized, cb, 0);
 
         self
     }
@@ -42,7 +160,32 @@ impl Builder {
             + 'static,
     {
         let cb = Callback::with_async(cb);
-        self.callbacks.insert(CallbackType::Initialized, cb);
+        self.callbacks.add(CallbackType::Initialized, cb, 0);
+
+        self
+    }
+
+    pub fn try_initialized<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        let cb = Callback::with_try_sync(cb);
+        self.callbacks.add(CallbackType::Initialized, cb, 0);
+
+        self
+    }
+
+    pub fn try_initialized_async<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = Result<(), E>> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+        E: Into<CallbackError> + 'static,
+    {
+        let cb = Callback::with_try_async(cb);
+        self.callbacks.add(CallbackType::Initialized, cb, 0);
 
         self
     }
@@ -54,9 +197,10 @@ impl Builder {
         F: Fn(CallbackInfo) + Send + Sync + 'static,
     {
         let cb = Callback::with_sync(cb);
-        self.callbacks.insert(CallbackType::ReloadConfig, cb);
+        self.callbacks.add(CallbackType::ReloadConfig, cb, 0);
 
-        self.registers.insert_reload_config();
+        self.registers
+            .get_or_insert_default(RegisterType::ReloadConfig);
 
         self
     }
@@ -70,9 +214,63 @@ impl Builder {
             + 'static,
     {
         let cb = Callback::with_async(cb);
-        self.callbacks.insert(CallbackType::ReloadConfig, cb);
+        self.callbacks.add(CallbackType::ReloadConfig, cb, 0);
 
-        self.registers.insert_reload_config();
+        self.registers
+            .get_or_insert_default(RegisterType::ReloadConfig);
+
+        self
+    }
+
+    #[cfg(not(windows))]
+    pub fn try_reload_config<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        let cb = Callback::with_try_sync(cb);
+        self.callbacks.add(CallbackType::ReloadConfig, cb, 0);
+
+        self.registers
+            .get_or_insert_default(RegisterType::ReloadConfig);
+
+        self
+    }
+
+    #[cfg(not(windows))]
+    pub fn try_reload_config_async<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = Result<(), E>> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+        E: Into<CallbackError> + 'static,
+    {
+        let cb = Callback::with_try_async(cb);
+        self.callbacks.add(CallbackType::ReloadConfig, cb, 0);
+
+        self.registers
+            .get_or_insert_default(RegisterType::ReloadConfig);
+
+        self
+    }
+
+    // Reloads on each reload_config signal, failures are routed to the on_error hook.
+    #[cfg(not(windows))]
+    pub fn config_reloader<T>(self, reloader: ConfigReloader<T>) -> Self
+    where
+        T: Send + Sync + 'static,
+    {
+        self.try_reload_config(move |_| reloader.reload())
+    }
+
+    // Changes of the files or directories are dispatched like the reload_config signals.
+    #[cfg(target_os = "linux")]
+    pub fn watch_path<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.watch_paths.push(path.as_ref().to_owned());
 
         self
     }
@@ -83,9 +281,10 @@ impl Builder {
         F: Fn(CallbackInfo) + Send + Sync + 'static,
     {
         let cb = Callback::with_sync(cb);
-        self.callbacks.insert(CallbackType::WaitForStop, cb);
+        self.callbacks.add(CallbackType::WaitForStop, cb, 0);
 
-        self.registers.insert_wait_for_stop();
+        self.registers
+            .get_or_insert_default(RegisterType::WaitForStop);
 
         self
     }
@@ -98,9 +297,119 @@ impl Builder {
             + 'static,
     {
         let cb = Callback::with_async(cb);
-        self.callbacks.insert(CallbackType::WaitForStop, cb);
+        self.callbacks.add(CallbackType::WaitForStop, cb, 0);
+
+        self.registers
+            .get_or_insert_default(RegisterType::WaitForStop);
+
+        self
+    }
+
+    pub fn try_wait_for_stop<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        let cb = Callback::with_try_sync(cb);
+        self.callbacks.add(CallbackType::WaitForStop, cb, 0);
+
+        self.registers
+            .get_or_insert_default(RegisterType::WaitForStop);
+
+        self
+    }
+
+    pub fn try_wait_for_stop_async<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = Result<(), E>> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+        E: Into<CallbackError> + 'static,
+    {
+        let cb = Callback::with_try_async(cb);
+        self.callbacks.add(CallbackType::WaitForStop, cb, 0);
+
+        self.registers
+            .get_or_insert_default(RegisterType::WaitForStop);
+
+        self
+    }
+
+    pub fn fast_stop<F>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) + Send + Sync + 'static,
+    {
+        let cb = Callback::with_sync(cb);
+        self.callbacks.add(CallbackType::FastStop, cb, 0);
+
+        self
+    }
+
+    pub fn fast_stop_async<F>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let cb = Callback::with_async(cb);
+        self.callbacks.add(CallbackType::FastStop, cb, 0);
 
-        self.registers.insert_wait_for_stop();
+        self
+    }
+
+    pub fn try_fast_stop<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        let cb = Callback::with_try_sync(cb);
+        self.callbacks.add(CallbackType::FastStop, cb, 0);
+
+        self
+    }
+
+    pub fn try_fast_stop_async<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = Result<(), E>> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+        E: Into<CallbackError> + 'static,
+    {
+        let cb = Callback::with_try_async(cb);
+        self.callbacks.add(CallbackType::FastStop, cb, 0);
+
+        self
+    }
+
+    pub fn stop_steps<I>(mut self, steps: I) -> Self
+    where
+        I: IntoIterator<Item = StopStep>,
+    {
+        self.stop_ladder.set_steps(steps);
+
+        self
+    }
+
+    pub fn on_stop_escalation<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(StopStep, Option<StopStep>) + Send + Sync + 'static,
+    {
+        self.stop_ladder.set_hook(Arc::new(hook));
+
+        self
+    }
+
+    pub fn stop_timeout(mut self, timeout: Duration) -> Self {
+        self.stop_timeout = Some(timeout);
+
+        self
+    }
+
+    pub fn stop_timeout_exit_code(mut self, code: i32) -> Self {
+        self.stop_timeout_exit_code = Some(code);
 
         self
     }
@@ -112,9 +421,10 @@ impl Builder {
         F: Fn(CallbackInfo) + Send + Sync + 'static,
     {
         let cb = Callback::with_sync(cb);
-        self.callbacks.insert(CallbackType::PrintStats, cb);
+        self.callbacks.add(CallbackType::PrintStats, cb, 0);
 
-        self.registers.insert_print_stats();
+        self.registers
+            .get_or_insert_default(RegisterType::PrintStats);
 
         self
     }
@@ -128,9 +438,143 @@ impl Builder {
             + 'static,
     {
         let cb = Callback::with_async(cb);
-        self.callbacks.insert(CallbackType::PrintStats, cb);
+        self.callbacks.add(CallbackType::PrintStats, cb, 0);
+
+        self.registers
+            .get_or_insert_default(RegisterType::PrintStats);
+
+        self
+    }
+
+    #[cfg(not(windows))]
+    pub fn try_print_stats<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        let cb = Callback::with_try_sync(cb);
+        self.callbacks.add(CallbackType::PrintStats, cb, 0);
+
+        self.registers
+            .get_or_insert_default(RegisterType::PrintStats);
+
+        self
+    }
+
+    #[cfg(not(windows))]
+    pub fn try_print_stats_async<F, E>(mut self, cb: F) -> Self
+    where
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = Result<(), E>> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+        E: Into<CallbackError> + 'static,
+    {
+        let cb = Callback::with_try_async(cb);
+        self.callbacks.add(CallbackType::PrintStats, cb, 0);
+
+        self.registers
+            .get_or_insert_default(RegisterType::PrintStats);
+
+        self
+    }
+
+    // The built-in print_stats callback, write errors are routed to the on_error hook.
+    #[cfg(not(windows))]
+    pub fn print_process_stats<W>(self, format: StatsFormat, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let printer = StatsPrinter::new(format, writer);
+        self.try_print_stats(move |info| printer.print(&info))
+    }
+
+    // Renders the registered metrics, e.g. to `AtomicFile` for the textfile collector of node_exporter.
+    #[cfg(not(windows))]
+    pub fn print_metrics<W>(self, registry: MetricsRegistry, format: StatsFormat, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let printer = StatsPrinter::new(format, writer);
+        self.try_print_stats(move |_| printer.print_metrics(&registry))
+    }
+
+    // Must be handled on a tokio runtime, e.g. with `handle_async_with_tokio`.
+    #[cfg(all(feature = "impl_tokio_metrics", not(windows)))]
+    pub fn print_tokio_stats<W>(self, format: StatsFormat, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let printer = StatsPrinter::new(format, writer);
+        self.try_print_stats(move |info| printer.print_tokio_runtime(&info))
+    }
+
+    //
+    pub fn on_signal<I, F>(mut self, name: &'static str, signal_numbers: I, cb: F) -> Self
+    where
+        I: IntoIterator<Item = SignalNumber>,
+        F: Fn(CallbackInfo) + Send + Sync + 'static,
+    {
+        let cb = Callback::with_sync(cb);
+        self.callbacks.add(CallbackType::Custom(name), cb, 0);
+
+        self.registers
+            .extend_signal_numbers(RegisterType::Custom(name), signal_numbers);
+
+        self
+    }
+
+    pub fn on_signal_async<I, F>(mut self, name: &'static str, signal_numbers: I, cb: F) -> Self
+    where
+        I: IntoIterator<Item = SignalNumber>,
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = ()> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let cb = Callback::with_async(cb);
+        self.callbacks.add(CallbackType::Custom(name), cb, 0);
+
+        self.registers
+            .extend_signal_numbers(RegisterType::Custom(name), signal_numbers);
+
+        self
+    }
+
+    pub fn try_on_signal<I, F, E>(mut self, name: &'static str, signal_numbers: I, cb: F) -> Self
+    where
+        I: IntoIterator<Item = SignalNumber>,
+        F: Fn(CallbackInfo) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        let cb = Callback::with_try_sync(cb);
+        self.callbacks.add(CallbackType::Custom(name), cb, 0);
+
+        self.registers
+            .extend_signal_numbers(RegisterType::Custom(name), signal_numbers);
+
+        self
+    }
+
+    pub fn try_on_signal_async<I, F, E>(
+        mut self,
+        name: &'static str,
+        signal_numbers: I,
+        cb: F,
+    ) -> Self
+    where
+        I: IntoIterator<Item = SignalNumber>,
+        F: Fn(CallbackInfo) -> Pin<Box<dyn Future<Output = Result<(), E>> + Send + 'static>>
+            + Send
+            + Sync
+            + 'static,
+        E: Into<CallbackError> + 'static,
+    {
+        let cb = Callback::with_try_async(cb);
+        self.callbacks.add(CallbackType::Custom(name), cb, 0);
 
-        self.registers.insert_print_stats();
+        self.registers
+            .extend_signal_numbers(RegisterType::Custom(name), signal_numbers);
 
         self
     }
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use crate::callback::{CallbackError, CallbackType};
+
+//
+pub type CallbackErrorHook = Arc<dyn Fn(CallbackType, &CallbackError) + Send + Sync + 'static>;
+
+#[derive(Clone, Default)]
+pub struct ErrorHandling {
+    hook: Option<CallbackErrorHook>,
+}
+
+impl core::fmt::Debug for ErrorHandling {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ErrorHandling")
+            .field("hook", &self.hook.as_ref().map(|_| "CallbackErrorHook"))
+            .finish()
+    }
+}
+
+impl ErrorHandling {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_hook(&mut self, hook: CallbackErrorHook) {
+        self.hook = Some(hook);
+    }
+
+    pub(crate) fn caught(&self, tp: CallbackType, err: &CallbackError) {
+        if let Some(hook) = &self.hook {
+            hook(tp, err);
+        }
+    }
+}
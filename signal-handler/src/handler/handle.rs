@@ -0,0 +1,217 @@
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, RwLock},
+};
+
+#[cfg(feature = "systemd")]
+use std::io::Error as IoError;
+
+use channel_sender::{generic::CloneableSender, SendError};
+
+#[cfg(feature = "systemd")]
+use crate::systemd::{Notifier, Watchdog};
+use crate::{
+    callback::{Callback, CallbackType, Callbacks, CoalescePolicy, ListenerId, Listeners},
+    handler::{stats::HandlerStats, HandleError, ShutdownToken},
+    register::{RegisterEvent, RegisterType},
+};
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lifecycle {
+    Ready,
+    Reloading,
+    Stopping,
+    Watchdog,
+}
+
+//
+type BoxSender = Box<dyn CloneableSender<RegisterEvent> + Send + Sync>;
+
+#[derive(Clone)]
+pub struct HandlerHandle {
+    callbacks: Arc<RwLock<Callbacks>>,
+    // The types whose signals are registered by the Builder.
+    registered: Arc<HashSet<RegisterType>>,
+    sender: Arc<Mutex<Option<BoxSender>>>,
+    sync_only: Arc<AtomicBool>,
+    shutdown_token: ShutdownToken,
+    stats: Arc<Mutex<HandlerStats>>,
+    #[cfg(feature = "systemd")]
+    notifier: Option<Notifier>,
+    #[cfg(feature = "systemd")]
+    watchdog: Option<Watchdog>,
+}
+
+impl core::fmt::Debug for HandlerHandle {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("HandlerHandle")
+            .field("callbacks", &self.callbacks)
+            .field("running", &self.is_running())
+            .field("shutdown_token", &self.shutdown_token)
+            .finish()
+    }
+}
+
+impl HandlerHandle {
+    pub(crate) fn new(callbacks: Callbacks, registered: HashSet<RegisterType>) -> Self {
+        Self {
+            callbacks: Arc::new(RwLock::new(callbacks)),
+            registered: Arc::new(registered),
+            sender: Default::default(),
+            sync_only: Default::default(),
+            shutdown_token: ShutdownToken::new(),
+            stats: Default::default(),
+            #[cfg(feature = "systemd")]
+            notifier: None,
+            #[cfg(feature = "systemd")]
+            watchdog: None,
+        }
+    }
+
+    #[cfg(feature = "systemd")]
+    pub(crate) fn with_systemd(
+        mut self,
+        notifier: Option<Notifier>,
+        watchdog: Option<Watchdog>,
+    ) -> Self {
+        self.notifier = notifier;
+        self.watchdog = watchdog;
+
+        self
+    }
+
+    pub fn shutdown_token(&self) -> ShutdownToken {
+        self.shutdown_token.clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.sender.lock().expect("lock poisoned").is_some()
+    }
+
+    pub fn stats(&self) -> HandlerStats {
+        self.stats.lock().expect("lock poisoned").clone()
+    }
+
+    #[cfg(feature = "systemd")]
+    pub fn notifier(&self) -> Option<&Notifier> {
+        self.notifier.as_ref()
+    }
+
+    // Shown by `systemctl status`, does nothing without a notifier.
+    #[cfg(feature = "systemd")]
+    pub fn notify_status(&self, status: &str) -> Result<(), IoError> {
+        match &self.notifier {
+            Some(notifier) => notifier.status(status),
+            None => Ok(()),
+        }
+    }
+
+    // Dispatched exactly like the signals of the type, fails unless the handler is running.
+    pub fn trigger(&self, tp: RegisterType) -> Result<(), SendError<RegisterEvent>> {
+        let event = RegisterEvent::new(tp);
+
+        match self.sender.lock().expect("lock poisoned").as_ref() {
+            Some(sender) => sender.send(event),
+            None => Err(SendError::Closed(event)),
+        }
+    }
+
+    pub fn shutdown(&self) -> Result<(), SendError<RegisterEvent>> {
+        self.trigger(RegisterType::WaitForStop)
+    }
+
+    pub fn add_callback(
+        &self,
+        tp: CallbackType,
+        priority: i32,
+        cb: Callback,
+    ) -> Result<ListenerId, HandleError> {
+        let mut callbacks = self.callbacks.write().expect("lock poisoned");
+
+        if matches!(cb, Callback::Async(_)) && self.sync_only.load(Ordering::SeqCst) {
+            return Err(HandleError::AsyncRequired);
+        }
+
+        // Signals cannot be registered at runtime, e.g. an unregistered SIGHUP would still terminate.
+        if let Some(register_tp) = tp.register_type() {
+            if !register_tp.signal_numbers().is_empty() && !self.registered.contains(&register_tp) {
+                return Err(HandleError::NotRegistered(tp));
+            }
+        }
+
+        Ok(callbacks.add(tp, cb, priority))
+    }
+
+    pub fn remove_callback(&self, id: ListenerId) -> bool {
+        self.callbacks.write().expect("lock poisoned").remove(id)
+    }
+
+    //
+    pub(crate) fn listeners(&self, tp: &CallbackType) -> Option<Listeners> {
+        self.callbacks.read().expect("lock poisoned").listeners(tp)
+    }
+
+    pub(crate) fn coalesce(&self, tp: &CallbackType) -> CoalescePolicy {
+        self.callbacks.read().expect("lock poisoned").coalesce(tp)
+    }
+
+    pub(crate) fn set_coalesce(&self, tp: CallbackType, coalesce: CoalescePolicy) {
+        self.callbacks
+            .write()
+            .expect("lock poisoned")
+            .set_coalesce(tp, coalesce);
+    }
+
+    // Best effort, like sd_notify. Does nothing without the systemd feature.
+    pub(crate) fn notify_lifecycle(&self, lifecycle: Lifecycle) {
+        #[cfg(feature = "systemd")]
+        if let Some(notifier) = &self.notifier {
+            let _ = match lifecycle {
+                Lifecycle::Ready => notifier.ready(),
+                Lifecycle::Reloading => notifier.reloading(),
+                Lifecycle::Stopping => notifier.stopping(),
+                Lifecycle::Watchdog => match &self.watchdog {
+                    Some(watchdog) if watchdog.is_healthy() => notifier.watchdog(),
+                    _ => Ok(()),
+                },
+            };
+        }
+        #[cfg(not(feature = "systemd"))]
+        let _ = lifecycle;
+    }
+
+    // None without a watchdog or without a notifier to ping.
+    pub(crate) fn watchdog_interval(&self) -> Option<Duration> {
+        #[cfg(feature = "systemd")]
+        if self.notifier.is_some() {
+            return self.watchdog.as_ref().map(|x| x.interval());
+        }
+
+        None
+    }
+
+    pub(crate) fn shared_stats(&self) -> Arc<Mutex<HandlerStats>> {
+        self.stats.clone()
+    }
+
+    pub(crate) fn set_sync_only(&self) -> Result<(), HandleError> {
+        let callbacks = self.callbacks.write().expect("lock poisoned");
+
+        if callbacks.has_async() {
+            return Err(HandleError::AsyncRequired);
+        }
+
+        self.sync_only.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    pub(crate) fn set_sender(&self, sender: Option<BoxSender>) {
+        *self.sender.lock().expect("lock poisoned") = sender;
+    }
+}
@@ -0,0 +1,879 @@
+use core::{
+    any::Any,
+    future::Future,
+    pin::{pin, Pin},
+    task::{Context, Poll},
+};
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread,
+    time::{Instant, SystemTime},
+};
+
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+use channel_sender::{
+    generic::{CloneableSender, Sender},
+    SendError,
+};
+use futures_channel::{
+    mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+use futures_util::{
+    future::{AbortHandle, Abortable, FusedFuture},
+    select,
+    stream::FusedStream,
+    FutureExt as _, Stream, StreamExt as _,
+};
+
+#[cfg(target_os = "linux")]
+use crate::reload::watch::FileWatcher;
+use crate::{
+    callback::{
+        Callback, CallbackError, CallbackInfo, CallbackType, CoalescePolicy, ExecutionMode,
+        Listeners,
+    },
+    handler::{
+        builder::Builder,
+        handle::Lifecycle,
+        outcome::{CallbackRecorder, HandleOutcome},
+        panic::{PanicHandling, PanicPolicy},
+        spawn::Spawn,
+        stop::{force_stop, stop_timed_out, StopStep},
+        timer::sleep_until_deadline,
+        HandleError, Handler, HandlerHandle,
+    },
+    register::{
+        PendingSender, PendingSignals, RegisterEvent, RegisterType, Registers, Registration,
+    },
+};
+
+//
+type Spawner = Arc<dyn Spawn + Send + Sync + 'static>;
+type PanicPayload = Box<dyn Any + Send + 'static>;
+
+#[derive(Debug, Clone)]
+pub(crate) struct RegisterSender(pub(crate) UnboundedSender<RegisterEvent>);
+
+impl Sender<RegisterEvent> for RegisterSender {
+    fn send(&self, t: RegisterEvent) -> Result<(), SendError<RegisterEvent>> {
+        self.0
+            .unbounded_send(t)
+            .map_err(|err| SendError::Closed(err.into_inner()))
+    }
+}
+
+impl CloneableSender<RegisterEvent> for RegisterSender {
+    fn send(&self, t: RegisterEvent) -> Result<(), SendError<RegisterEvent>> {
+        Sender::send(self, t)
+    }
+}
+
+// The signals are taken from the PendingSignals, the triggers and file changes come from the channel.
+// Never ends, signals are still received after the channel closed, e.g. while stopping.
+#[derive(Debug)]
+pub(crate) struct RegisterReceiver {
+    signals: Arc<PendingSignals>,
+    rx: UnboundedReceiver<RegisterEvent>,
+}
+
+impl Stream for RegisterReceiver {
+    type Item = RegisterEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if let Poll::Ready(event) = this.signals.poll_take(cx) {
+            return Poll::Ready(Some(event));
+        }
+
+        match this.rx.poll_next_unpin(cx) {
+            Poll::Ready(Some(event)) => Poll::Ready(Some(event)),
+            Poll::Ready(None) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl FusedStream for RegisterReceiver {
+    fn is_terminated(&self) -> bool {
+        false
+    }
+}
+
+pub(crate) fn register(
+    registers: Registers,
+    restore_default: bool,
+) -> Result<(RegisterSender, RegisterReceiver, Registration), HandleError> {
+    let (tx, rx) = unbounded::<RegisterEvent>();
+    let signals = PendingSignals::new(&registers);
+
+    let mut registration = registers
+        .register(PendingSender(signals.clone()))
+        .map_err(HandleError::RegisterFailed)?;
+    registration.set_restore_default(restore_default);
+
+    Ok((
+        RegisterSender(tx),
+        RegisterReceiver { signals, rx },
+        registration,
+    ))
+}
+
+// File changes are sent like the reload_config signals, so that they are coalesced the same way.
+#[cfg(target_os = "linux")]
+pub(crate) fn watch_paths_for_reload(
+    paths: &[PathBuf],
+    tx: RegisterSender,
+) -> Result<Option<FileWatcher>, HandleError> {
+    if paths.is_empty() {
+        return Ok(None);
+    }
+
+    FileWatcher::new(paths, move || {
+        Sender::send(&tx, RegisterEvent::new(RegisterType::ReloadConfig)).is_ok()
+    })
+    .map(Some)
+    .map_err(|err| HandleError::Other(err.into()))
+}
+
+//
+struct JoinHandle {
+    abort_handle: Option<AbortHandle>,
+    rx: oneshot::Receiver<thread::Result<()>>,
+}
+
+impl JoinHandle {
+    fn spawn<F>(spawner: &Spawner, future: F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+
+        spawner.spawn(Box::pin(async move {
+            let future = AssertUnwindSafe(future).catch_unwind();
+            if let Ok(ret) = Abortable::new(future, abort_registration).await {
+                let _ = tx.send(ret);
+            }
+        }));
+
+        Self {
+            abort_handle: Some(abort_handle),
+            rx,
+        }
+    }
+
+    fn spawn_blocking<F>(spawner: &Spawner, f: F) -> Self
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        spawner.spawn_blocking(Box::new(move || {
+            let _ = tx.send(panic::catch_unwind(AssertUnwindSafe(f)));
+        }));
+
+        Self {
+            abort_handle: None,
+            rx,
+        }
+    }
+
+    async fn join(&mut self) -> thread::Result<()> {
+        // Canceled only if the task was aborted.
+        (&mut self.rx).await.unwrap_or(Ok(()))
+    }
+
+    fn abort(self) {
+        if let Some(abort_handle) = self.abort_handle {
+            abort_handle.abort();
+        }
+    }
+}
+
+//
+impl Handler {
+    pub async fn handle_async<S>(self, spawner: S) -> Result<HandleOutcome, HandleError>
+    where
+        S: Spawn + Send + Sync + 'static,
+    {
+        let spawner: Spawner = Arc::new(spawner);
+
+        let Builder {
+            callbacks: _,
+            registers,
+            stop_ladder,
+            stop_timeout,
+            stop_timeout_exit_code,
+            restore_default_on_exit,
+            panic_handling,
+            error_handling,
+            #[cfg(target_os = "linux")]
+            watch_paths,
+            // The systemd notifier and watchdog were moved into the HandlerHandle.
+            ..
+        } = self.builder;
+        let handle = self.handle;
+        let shutdown_token = handle.shutdown_token();
+
+        let started_at = SystemTime::now();
+        let recorder = CallbackRecorder::new(handle.shared_stats(), error_handling);
+        recorder.started_handler();
+
+        //
+        //
+        //
+        let (register_tx, mut register_rx, registration) =
+            register(registers, restore_default_on_exit)?;
+
+        #[cfg(target_os = "linux")]
+        let file_watcher = watch_paths_for_reload(&watch_paths, register_tx.clone())?;
+
+        handle.set_sender(Some(Box::new(register_tx)));
+
+        // Panics to be propagated by the workers.
+        let (panic_tx, mut panic_rx) = unbounded::<PanicPayload>();
+
+        //
+        //
+        //
+        let mut callback_tx_map = HashMap::new();
+        let mut callback_join_handle_map = HashMap::new();
+
+        let spawn_worker = |tp: CallbackType| {
+            let (tx, rx) = unbounded::<CallbackInfo>();
+
+            let worker = Worker {
+                spawner: spawner.clone(),
+                handle: handle.clone(),
+                recorder: recorder.clone(),
+                panic_handling: panic_handling.clone(),
+                panic_tx: panic_tx.clone(),
+            };
+            let join_handle = JoinHandle::spawn(&spawner, worker.run(tp, rx));
+
+            (tx, join_handle)
+        };
+
+        //
+        //
+        //
+        if let Some(cb) = handle.listeners(&CallbackType::Initialized) {
+            recorder.started(CallbackType::Initialized);
+            let instant = Instant::now();
+            let ret = AssertUnwindSafe(call_listeners(&spawner, &cb, CallbackInfo::new()))
+                .catch_unwind()
+                .await;
+            recorder.record(CallbackType::Initialized, instant.elapsed());
+
+            match ret {
+                Ok(errors) => {
+                    recorder.record_errors(CallbackType::Initialized, &errors);
+
+                    // Aborts before handling any signal, the registration is dropped on return.
+                    if let Some(err) = errors.into_iter().next() {
+                        handle.set_sender(None);
+                        shutdown_token.cancel();
+
+                        return Err(HandleError::Other(err));
+                    }
+                }
+                Err(payload) => {
+                    recorder.record_panic(CallbackType::Initialized);
+
+                    match panic_handling.caught(CallbackType::Initialized, &*payload) {
+                        PanicPolicy::KeepRunning => {}
+                        PanicPolicy::StopHandler => {
+                            let _ = handle.shutdown();
+                        }
+                        PanicPolicy::Propagate => propagate(&handle, payload),
+                    }
+                }
+            }
+        }
+
+        handle.notify_lifecycle(Lifecycle::Ready);
+
+        //
+        //
+        //
+        let mut stop_event = None;
+        let mut stop_requested_at = None;
+
+        let watchdog_interval = handle.watchdog_interval();
+        let mut watchdog_tick = pin!(sleep_until_deadline(
+            watchdog_interval.map(|x| Instant::now() + x)
+        ));
+
+        loop {
+            let event = select! {
+                event = register_rx.next() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                payload = panic_rx.next() => {
+                    if let Some(payload) = payload {
+                        propagate(&handle, payload);
+                    }
+                    continue;
+                }
+                _ = watchdog_tick => {
+                    handle.notify_lifecycle(Lifecycle::Watchdog);
+                    watchdog_tick.set(sleep_until_deadline(
+                        watchdog_interval.map(|x| Instant::now() + x),
+                    ));
+                    continue;
+                }
+            };
+
+            let tp = CallbackType::from(event.tp);
+            recorder.received(tp);
+
+            if event.tp == RegisterType::WaitForStop {
+                stop_event = Some(event);
+                stop_requested_at = Some(SystemTime::now());
+                shutdown_token.cancel();
+                handle.notify_lifecycle(Lifecycle::Stopping);
+
+                break;
+            }
+
+            // Workers are spawned on demand, callbacks can be added at runtime.
+            if !callback_tx_map.contains_key(&tp) && handle.listeners(&tp).is_some() {
+                let (tx, join_handle) = spawn_worker(tp);
+                callback_tx_map.insert(tp, tx);
+                callback_join_handle_map.insert(tp, join_handle);
+            }
+
+            if let Some(tx_callback) = callback_tx_map.get(&tp) {
+                #[allow(clippy::single_match)]
+                match tx_callback.unbounded_send(CallbackInfo::with_event(&event)) {
+                    Ok(_) => {}
+                    Err(_) => {
+                        // Ignore, disconnected
+                    }
+                }
+            }
+        }
+
+        //
+        //
+        //
+        let mut deadline = None;
+        let mut timed_out = false;
+        // The step of the latest stop signal, the ladder keeps advancing until the workers are joined.
+        let mut n = 0;
+
+        if let Some(mut event) = stop_event {
+            deadline = stop_timeout.map(|x| Instant::now() + x);
+
+            let mut stop_join_handle: Option<(CallbackType, JoinHandle)> = None;
+            let mut next_step = stop_ladder.escalate(n);
+            let mut expired = pin!(sleep_until_deadline(deadline));
+
+            loop {
+                if let Some(step) = next_step.take() {
+                    if let Some((_, join_handle)) = stop_join_handle.take() {
+                        // Sync callbacks cannot be cancelled, they are abandoned.
+                        join_handle.abort();
+                    }
+
+                    let info = CallbackInfo {
+                        deadline,
+                        ..CallbackInfo::with_event(&event)
+                    };
+
+                    stop_join_handle = match step {
+                        StopStep::Graceful => {
+                            handle.listeners(&CallbackType::WaitForStop).map(|cb| {
+                                let tp = CallbackType::WaitForStop;
+                                (tp, spawn_callback(&spawner, cb, tp, info, &recorder))
+                            })
+                        }
+                        StopStep::Fast => handle.listeners(&CallbackType::FastStop).map(|cb| {
+                            let tp = CallbackType::FastStop;
+                            (tp, spawn_callback(&spawner, cb, tp, info, &recorder))
+                        }),
+                        StopStep::Force => {
+                            force_stop(event.signal_number);
+                            None
+                        }
+                    };
+                }
+
+                let (tp, join_handle) = match stop_join_handle.as_mut() {
+                    Some((tp, join_handle)) => (*tp, join_handle),
+                    None => break,
+                };
+
+                select! {
+                    ret = join_handle.join().fuse() => {
+                        if let Err(payload) = ret {
+                            recorder.record_panic(tp);
+
+                            // Stopping anyway.
+                            if panic_handling.caught(tp, &*payload) == PanicPolicy::Propagate {
+                                propagate(&handle, payload);
+                            }
+                        }
+
+                        break;
+                    }
+                    payload = panic_rx.next() => {
+                        if let Some(payload) = payload {
+                            propagate(&handle, payload);
+                        }
+                    }
+                    ret = register_rx.next() => {
+                        match ret {
+                            Some(escalation_event) if escalation_event.tp == RegisterType::WaitForStop => {
+                                recorder.received(CallbackType::WaitForStop);
+
+                                event = escalation_event;
+                                n += 1;
+                                next_step = stop_ladder.escalate(n);
+                            }
+                            Some(other_event) => {
+                                // Stopping
+                                let tp = CallbackType::from(other_event.tp);
+                                recorder.received(tp);
+                                recorder.dropped(tp);
+                            }
+                            None => {}
+                        }
+                    }
+                    _ = expired => {
+                        if let Some((_, join_handle)) = stop_join_handle.take() {
+                            join_handle.abort();
+                        }
+                        timed_out = true;
+
+                        break;
+                    }
+                }
+            }
+        }
+
+        // The registration is kept until the workers are joined, see below.
+        handle.set_sender(None);
+        #[cfg(target_os = "linux")]
+        drop(file_watcher);
+        shutdown_token.cancel();
+
+        //
+        //
+        //
+        for (_, tx) in callback_tx_map {
+            drop(tx);
+        }
+
+        let deadline = deadline.or_else(|| stop_timeout.map(|x| Instant::now() + x));
+        let mut expired = pin!(sleep_until_deadline(deadline));
+
+        for (_, mut join_handle) in callback_join_handle_map {
+            // A finished deadline is skipped by select!, the remaining workers are abandoned.
+            if expired.is_terminated() {
+                join_handle.abort();
+                timed_out = true;
+
+                continue;
+            }
+
+            let ret = loop {
+                select! {
+                    ret = join_handle.join().fuse() => break ret,
+                    // A hung worker must not swallow the stop signals.
+                    ret = register_rx.next() => {
+                        match ret {
+                            Some(event) if event.tp == RegisterType::WaitForStop => {
+                                recorder.received(CallbackType::WaitForStop);
+
+                                // The stop callbacks are done, only a Force step is left to take.
+                                n += 1;
+                                match stop_ladder.escalate(n) {
+                                    Some(StopStep::Force) => force_stop(event.signal_number),
+                                    _ => recorder.dropped(CallbackType::WaitForStop),
+                                }
+                            }
+                            Some(other_event) => {
+                                // Stopping
+                                let tp = CallbackType::from(other_event.tp);
+                                recorder.received(tp);
+                                recorder.dropped(tp);
+                            }
+                            None => {}
+                        }
+                    }
+                    _ = expired => {
+                        join_handle.abort();
+                        timed_out = true;
+
+                        break Ok(());
+                    }
+                }
+            };
+
+            match ret {
+                Ok(_) => {}
+                Err(err) => {
+                    panic::resume_unwind(err);
+                }
+            }
+        }
+
+        drop(registration);
+        drop(register_rx);
+
+        // An abandoned worker may still hold a sender.
+        if let Ok(payload) = panic_rx.try_recv() {
+            panic::resume_unwind(payload);
+        }
+
+        if timed_out {
+            return Err(stop_timed_out(stop_timeout_exit_code));
+        }
+
+        Ok(recorder.outcome(stop_event.as_ref(), started_at, stop_requested_at))
+    }
+}
+
+struct Worker {
+    spawner: Spawner,
+    handle: HandlerHandle,
+    recorder: CallbackRecorder,
+    panic_handling: PanicHandling,
+    panic_tx: UnboundedSender<PanicPayload>,
+}
+
+impl Worker {
+    async fn run(self, tp: CallbackType, mut rx: UnboundedReceiver<CallbackInfo>) {
+        let mut latest_start = None;
+        let mut latest_finish = None;
+
+        while let Some(mut info) = rx.next().await {
+            // Looked up on each call, callbacks can be changed at runtime.
+            let cb = match self.handle.listeners(&tp) {
+                Some(cb) => cb,
+                None => {
+                    self.recorder.dropped(tp);
+                    continue;
+                }
+            };
+
+            match cb.coalesce() {
+                CoalescePolicy::CoalesceWhileRunning => {
+                    take_latest(&mut rx, &mut info);
+                }
+                CoalescePolicy::Queue => {}
+                CoalescePolicy::DropWhileRunning => {
+                    if let Some(latest_finish) = latest_finish {
+                        if latest_finish > *info.instant() {
+                            self.recorder.dropped(tp);
+                            continue;
+                        }
+                    }
+                }
+                CoalescePolicy::Debounce(quiet) => loop {
+                    sleep_until_deadline(Some(Instant::now() + quiet)).await;
+                    if !take_latest(&mut rx, &mut info) {
+                        break;
+                    }
+                },
+                CoalescePolicy::Throttle(interval) => {
+                    if let Some(latest_start) = latest_start {
+                        sleep_until_deadline(Some(latest_start + interval)).await;
+                    }
+                    take_latest(&mut rx, &mut info);
+                }
+            }
+
+            if info.coalesced > 0 {
+                self.recorder.coalesced(tp, info.coalesced);
+            }
+            if tp == CallbackType::PrintStats {
+                info.stats = Some(Arc::new(self.recorder.stats()));
+            }
+
+            if tp == CallbackType::ReloadConfig {
+                self.handle.notify_lifecycle(Lifecycle::Reloading);
+            }
+
+            self.recorder.started(tp);
+            let instant = Instant::now();
+            let ret = AssertUnwindSafe(call_listeners(&self.spawner, &cb, info))
+                .catch_unwind()
+                .await;
+            self.recorder.record(tp, instant.elapsed());
+
+            // Ready again even if the reload failed, the previous config is still in use.
+            if tp == CallbackType::ReloadConfig {
+                self.handle.notify_lifecycle(Lifecycle::Ready);
+            }
+
+            latest_start = Some(instant);
+            latest_finish = Some(Instant::now());
+
+            match ret {
+                Ok(errors) => self.recorder.record_errors(tp, &errors),
+                // The worker survives, later signals of the type are still handled.
+                Err(payload) => {
+                    self.recorder.record_panic(tp);
+
+                    match self.panic_handling.caught(tp, &*payload) {
+                        PanicPolicy::KeepRunning => {}
+                        PanicPolicy::StopHandler => {
+                            let _ = self.handle.shutdown();
+                        }
+                        PanicPolicy::Propagate => {
+                            let _ = self.panic_tx.unbounded_send(payload);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Replaces the info with the latest queued one, returns whether any was queued.
+fn take_latest(rx: &mut UnboundedReceiver<CallbackInfo>, info: &mut CallbackInfo) -> bool {
+    let mut taken = false;
+    while let Ok(latest) = rx.try_recv() {
+        *info = CallbackInfo {
+            coalesced: info.coalesced + 1 + latest.coalesced,
+            ..latest
+        };
+        taken = true;
+    }
+
+    taken
+}
+
+fn spawn_callback(
+    spawner: &Spawner,
+    cb: Listeners,
+    tp: CallbackType,
+    info: CallbackInfo,
+    recorder: &CallbackRecorder,
+) -> JoinHandle {
+    let recorder = recorder.clone();
+    recorder.started(tp);
+    let instant = Instant::now();
+
+    if cb.has_async() || cb.mode() == ExecutionMode::Concurrent {
+        let spawner_inner = spawner.clone();
+        JoinHandle::spawn(spawner, async move {
+            let errors = call_listeners(&spawner_inner, &cb, info).await;
+            recorder.record(tp, instant.elapsed());
+            recorder.record_errors(tp, &errors);
+        })
+    } else {
+        JoinHandle::spawn_blocking(spawner, move || {
+            let errors = cb.call_sync(info);
+            recorder.record(tp, instant.elapsed());
+            recorder.record_errors(tp, &errors);
+        })
+    }
+}
+
+// A failed callback does not prevent the others from running.
+async fn call_listeners(
+    spawner: &Spawner,
+    cb: &Listeners,
+    info: CallbackInfo,
+) -> Vec<CallbackError> {
+    let mut errors = vec![];
+
+    match cb.mode() {
+        ExecutionMode::Sequential => {
+            for cb in cb.callbacks() {
+                let ret = match cb {
+                    Callback::Sync(cb) => cb(info.clone()),
+                    Callback::Async(cb) => cb(info.clone()).await,
+                };
+                if let Err(err) = ret {
+                    errors.push(err);
+                }
+            }
+        }
+        ExecutionMode::Concurrent => {
+            let (errors_tx, mut errors_rx) = unbounded::<CallbackError>();
+
+            let join_handles = cb
+                .callbacks()
+                .map(|cb| {
+                    let errors_tx = errors_tx.clone();
+                    match cb.clone() {
+                        Callback::Sync(cb) => {
+                            let info = info.clone();
+                            JoinHandle::spawn_blocking(spawner, move || {
+                                if let Err(err) = cb(info) {
+                                    let _ = errors_tx.unbounded_send(err);
+                                }
+                            })
+                        }
+                        Callback::Async(cb) => {
+                            let fut = cb(info.clone());
+                            JoinHandle::spawn(spawner, async move {
+                                if let Err(err) = fut.await {
+                                    let _ = errors_tx.unbounded_send(err);
+                                }
+                            })
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            for mut join_handle in join_handles {
+                match join_handle.join().await {
+                    Ok(_) => {}
+                    Err(err) => {
+                        panic::resume_unwind(err);
+                    }
+                }
+            }
+
+            while let Ok(err) = errors_rx.try_recv() {
+                errors.push(err);
+            }
+        }
+    }
+
+    errors
+}
+
+// The registration is dropped while unwinding.
+fn propagate(handle: &HandlerHandle, payload: PanicPayload) -> ! {
+    handle.set_sender(None);
+    handle.shutdown_token().cancel();
+
+    panic::resume_unwind(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        sync::{mpsc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use crate::handler::{EventStats, Handler};
+
+    // A burst of 4 reloads, with `while_running` the last 3 are sent while the first one runs.
+    // Returns the stats and the coalesced count and the start of each invocation.
+    fn burst(coalesce: CoalescePolicy, while_running: bool) -> (EventStats, Vec<(usize, Instant)>) {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Mutex::new(release_rx);
+
+        let handler = Handler::builder()
+            .reload_config(move |info| {
+                started_tx.send((info.coalesced, Instant::now())).unwrap();
+                let _ = release_rx.lock().unwrap().recv();
+            })
+            .coalesce(CallbackType::ReloadConfig, coalesce)
+            .build();
+        let handle = handler.control_handle();
+        let join_handle = thread::spawn(move || handler.handle());
+        while !handle.is_running() {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let mut started = vec![];
+        if while_running {
+            handle.trigger(RegisterType::ReloadConfig).unwrap();
+            started.push(started_rx.recv_timeout(Duration::from_secs(5)).unwrap());
+        }
+        for _ in started.len()..4 {
+            handle.trigger(RegisterType::ReloadConfig).unwrap();
+        }
+        for _ in 0..100 {
+            let received = handle
+                .stats()
+                .event(CallbackType::ReloadConfig)
+                .map_or(0, |x| x.received);
+            if received >= 4 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        // Received is recorded just before the event is sent to the worker.
+        thread::sleep(Duration::from_millis(50));
+        drop(release_tx);
+
+        // Long enough for the follow-up invocations, the debounce and the throttle.
+        thread::sleep(Duration::from_millis(500));
+        let stats = handle
+            .stats()
+            .event(CallbackType::ReloadConfig)
+            .cloned()
+            .unwrap();
+        started.extend(started_rx.try_iter());
+
+        handle.shutdown().unwrap();
+        join_handle.join().unwrap().unwrap();
+
+        (stats, started)
+    }
+
+    #[test]
+    fn test_coalesce_while_running() {
+        let (stats, started) = burst(CoalescePolicy::CoalesceWhileRunning, true);
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.invocations, 2);
+        assert_eq!(stats.coalesced, 2);
+        assert_eq!(stats.dropped, 0);
+        assert_eq!(started.iter().map(|x| x.0).collect::<Vec<_>>(), [0, 2]);
+    }
+
+    #[test]
+    fn test_coalesce_queue() {
+        let (stats, started) = burst(CoalescePolicy::Queue, true);
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.invocations, 4);
+        assert_eq!(stats.coalesced, 0);
+        assert_eq!(stats.dropped, 0);
+        assert_eq!(
+            started.iter().map(|x| x.0).collect::<Vec<_>>(),
+            [0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_coalesce_drop_while_running() {
+        let (stats, started) = burst(CoalescePolicy::DropWhileRunning, true);
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.invocations, 1);
+        assert_eq!(stats.coalesced, 0);
+        assert_eq!(stats.dropped, 3);
+        assert_eq!(started.iter().map(|x| x.0).collect::<Vec<_>>(), [0]);
+    }
+
+    #[test]
+    fn test_coalesce_debounce() {
+        let (stats, started) = burst(CoalescePolicy::Debounce(Duration::from_millis(100)), false);
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.invocations, 1);
+        assert_eq!(stats.coalesced, 3);
+        assert_eq!(stats.dropped, 0);
+        assert_eq!(started.iter().map(|x| x.0).collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn test_coalesce_throttle() {
+        let interval = Duration::from_millis(200);
+        let start = Instant::now();
+        let (stats, started) = burst(CoalescePolicy::Throttle(interval), true);
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.invocations, 2);
+        assert_eq!(stats.coalesced, 2);
+        assert_eq!(stats.dropped, 0);
+        assert_eq!(started.iter().map(|x| x.0).collect::<Vec<_>>(), [0, 2]);
+        assert!(started[1].1 - start >= interval);
+    }
+}
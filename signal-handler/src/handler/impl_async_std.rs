@@ -0,0 +1,8 @@
+use crate::handler::{spawn::AsyncStdSpawn, HandleError, HandleOutcome, Handler};
+
+//
+impl Handler {
+    pub async fn handle_async_with_async_std(self) -> Result<HandleOutcome, HandleError> {
+        self.handle_async(AsyncStdSpawn).await
+    }
+}
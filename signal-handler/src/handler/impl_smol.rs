@@ -0,0 +1,8 @@
+use crate::handler::{spawn::SmolSpawn, HandleError, HandleOutcome, Handler};
+
+//
+impl Handler {
+    pub async fn handle_async_with_smol(self) -> Result<HandleOutcome, HandleError> {
+        self.handle_async(SmolSpawn).await
+    }
+}
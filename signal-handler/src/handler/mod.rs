@@ -1,18 +1,42 @@
-use crate::register::RegisterError;
+use crate::{callback::CallbackType, register::RegisterError};
 
 //
 pub mod builder;
+pub mod error;
+pub mod handle;
+pub mod outcome;
+pub mod panic;
+pub mod shutdown;
+pub mod spawn;
+pub mod stats;
+pub mod stop;
+pub mod stream;
 
+mod impl_async;
+#[cfg(feature = "impl_async_std")]
+mod impl_async_std;
+#[cfg(feature = "impl_smol")]
+mod impl_smol;
 mod impl_std;
 #[cfg(feature = "impl_tokio")]
 mod impl_tokio;
+mod timer;
 
 pub use builder::Builder;
+pub use handle::HandlerHandle;
+pub use outcome::HandleOutcome;
+pub use panic::PanicPolicy;
+pub use shutdown::ShutdownToken;
+pub use spawn::Spawn;
+pub use stats::{EventStats, HandlerStats};
+pub use stop::StopStep;
+pub use stream::{HandlerEvent, HandlerIter, HandlerStream};
 
 //
 #[derive(Debug)]
 pub struct Handler {
     builder: Builder,
+    handle: HandlerHandle,
 }
 
 impl Handler {
@@ -20,16 +44,40 @@ impl Handler {
         Builder::new()
     }
 
-    pub(crate) fn from_builder(builder: Builder) -> Self {
-        Self { builder }
+    pub(crate) fn from_builder(mut builder: Builder) -> Self {
+        // Callbacks are shared with the HandlerHandle, so that they can be changed at runtime.
+        let callbacks = core::mem::take(&mut builder.callbacks);
+        let registered = builder
+            .registers
+            .iter()
+            .filter(|(_, signal_numbers)| !signal_numbers.is_empty())
+            .map(|(tp, _)| *tp)
+            .collect();
+        let handle = HandlerHandle::new(callbacks, registered);
+        #[cfg(feature = "systemd")]
+        let handle = handle.with_systemd(builder.notifier.take(), builder.watchdog.take());
+
+        Self { builder, handle }
+    }
+
+    pub fn shutdown_token(&self) -> ShutdownToken {
+        self.handle.shutdown_token()
+    }
+
+    pub fn control_handle(&self) -> HandlerHandle {
+        self.handle.clone()
     }
 }
 
 //
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum HandleError {
     AsyncRequired,
+    // Listeners added at runtime for a type whose signals were not registered by the Builder.
+    NotRegistered(CallbackType),
     RegisterFailed(RegisterError),
+    StopTimedOut,
     Other(Box<dyn std::error::Error + Send + Sync + 'static>),
 }
 
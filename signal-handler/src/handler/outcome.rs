@@ -0,0 +1,178 @@
+use core::time::Duration;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime},
+};
+
+use crate::{
+    callback::{CallbackError, CallbackType},
+    handler::{error::ErrorHandling, stats::HandlerStats},
+    register::{RegisterEvent, SignalNumber, SignalOrigin},
+};
+
+//
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct HandleOutcome {
+    // None if it stopped without a stop signal, e.g. triggered programmatically.
+    pub signal_number: Option<SignalNumber>,
+    pub origin: Option<SignalOrigin>,
+    pub started_at: SystemTime,
+    pub stop_requested_at: Option<SystemTime>,
+    pub finished_at: SystemTime,
+    pub callback_counts: HashMap<CallbackType, usize>,
+    pub callback_durations: HashMap<CallbackType, Duration>,
+    pub callback_errors: HashMap<CallbackType, usize>,
+    // Caught panics, not the propagated ones.
+    pub callback_panics: HashMap<CallbackType, usize>,
+}
+
+impl HandleOutcome {
+    pub fn signal_number(&self) -> Option<SignalNumber> {
+        self.signal_number
+    }
+
+    pub fn callback_count(&self, tp: CallbackType) -> usize {
+        self.callback_counts.get(&tp).copied().unwrap_or(0)
+    }
+
+    pub fn callback_duration(&self, tp: CallbackType) -> Duration {
+        self.callback_durations
+            .get(&tp)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn callback_error_count(&self, tp: CallbackType) -> usize {
+        self.callback_errors.get(&tp).copied().unwrap_or(0)
+    }
+
+    pub fn callback_panic_count(&self, tp: CallbackType) -> usize {
+        self.callback_panics.get(&tp).copied().unwrap_or(0)
+    }
+
+    pub fn reload_config_count(&self) -> usize {
+        self.callback_count(CallbackType::ReloadConfig)
+    }
+
+    pub fn print_stats_count(&self) -> usize {
+        self.callback_count(CallbackType::PrintStats)
+    }
+
+    // The conventional exit code of a process terminated by the signal.
+    pub fn exit_code(&self) -> i32 {
+        match self.signal_number {
+            Some(signal_number) => 128 + signal_number,
+            None => 0,
+        }
+    }
+}
+
+//
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CallbackRecorder {
+    stats: Arc<Mutex<HandlerStats>>,
+    error_handling: ErrorHandling,
+}
+
+impl CallbackRecorder {
+    pub(crate) fn new(stats: Arc<Mutex<HandlerStats>>, error_handling: ErrorHandling) -> Self {
+        Self {
+            stats,
+            error_handling,
+        }
+    }
+
+    pub(crate) fn stats(&self) -> HandlerStats {
+        self.stats.lock().expect("lock poisoned").clone()
+    }
+
+    pub(crate) fn started_handler(&self) {
+        let mut stats = self.stats.lock().expect("lock poisoned");
+        stats.started_at = Some(SystemTime::now());
+        stats.started_instant = Some(Instant::now());
+    }
+
+    pub(crate) fn received(&self, tp: CallbackType) {
+        let mut stats = self.stats.lock().expect("lock poisoned");
+        stats.entry(tp).received += 1;
+    }
+
+    pub(crate) fn coalesced(&self, tp: CallbackType, n: usize) {
+        let mut stats = self.stats.lock().expect("lock poisoned");
+        stats.entry(tp).coalesced += n as u64;
+    }
+
+    pub(crate) fn dropped(&self, tp: CallbackType) {
+        let mut stats = self.stats.lock().expect("lock poisoned");
+        stats.entry(tp).dropped += 1;
+    }
+
+    pub(crate) fn started(&self, tp: CallbackType) {
+        let mut stats = self.stats.lock().expect("lock poisoned");
+        let entry = stats.entry(tp);
+        entry.last_started_at = Some(SystemTime::now());
+        entry.last_started_instant = Some(Instant::now());
+    }
+
+    pub(crate) fn record(&self, tp: CallbackType, duration: Duration) {
+        let mut stats = self.stats.lock().expect("lock poisoned");
+        let entry = stats.entry(tp);
+        entry.invocations += 1;
+        entry.last_finished_at = Some(SystemTime::now());
+        entry.last_finished_instant = Some(Instant::now());
+        entry.last_duration = Some(duration);
+        entry.total_duration += duration;
+    }
+
+    pub(crate) fn record_errors(&self, tp: CallbackType, errors: &[CallbackError]) {
+        let last = match errors.last() {
+            Some(last) => last.to_string(),
+            None => return,
+        };
+
+        for err in errors {
+            self.error_handling.caught(tp, err);
+        }
+
+        let mut stats = self.stats.lock().expect("lock poisoned");
+        let entry = stats.entry(tp);
+        entry.errors += errors.len() as u64;
+        entry.last_error = Some(last);
+    }
+
+    pub(crate) fn record_panic(&self, tp: CallbackType) {
+        let mut stats = self.stats.lock().expect("lock poisoned");
+        stats.entry(tp).panics += 1;
+    }
+
+    pub(crate) fn outcome(
+        &self,
+        stop_event: Option<&RegisterEvent>,
+        started_at: SystemTime,
+        stop_requested_at: Option<SystemTime>,
+    ) -> HandleOutcome {
+        let stats = self.stats.lock().expect("lock poisoned");
+        let invoked = || {
+            stats
+                .events
+                .iter()
+                .filter(|(_, x)| x.invocations > 0 || x.panics > 0)
+        };
+
+        HandleOutcome {
+            signal_number: stop_event.and_then(|x| x.signal_number),
+            origin: stop_event.and_then(|x| x.origin),
+            started_at,
+            stop_requested_at,
+            finished_at: SystemTime::now(),
+            callback_counts: invoked()
+                .map(|(tp, x)| (*tp, x.invocations as usize))
+                .collect(),
+            callback_durations: invoked().map(|(tp, x)| (*tp, x.total_duration)).collect(),
+            callback_errors: invoked().map(|(tp, x)| (*tp, x.errors as usize)).collect(),
+            callback_panics: invoked().map(|(tp, x)| (*tp, x.panics as usize)).collect(),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use core::any::Any;
+use std::sync::Arc;
+
+use crate::callback::CallbackType;
+
+//
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PanicPolicy {
+    // Keeps handling signals, the next signal of the type invokes the callbacks again.
+    #[default]
+    KeepRunning,
+    // Stops the handler as if a stop signal was received.
+    StopHandler,
+    // Unregisters the signals and resumes the panic from `handle*`.
+    Propagate,
+}
+
+//
+pub type CallbackPanicHook = Arc<dyn Fn(CallbackType, &(dyn Any + Send)) + Send + Sync + 'static>;
+
+#[derive(Clone, Default)]
+pub struct PanicHandling {
+    // None for the default of the type, see `policy_for`.
+    policy: Option<PanicPolicy>,
+    hook: Option<CallbackPanicHook>,
+}
+
+impl core::fmt::Debug for PanicHandling {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PanicHandling")
+            .field("policy", &self.policy)
+            .field("hook", &self.hook.as_ref().map(|_| "CallbackPanicHook"))
+            .finish()
+    }
+}
+
+impl PanicHandling {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn policy(&self) -> Option<PanicPolicy> {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: PanicPolicy) {
+        self.policy = Some(policy);
+    }
+
+    // Unless set, panics of the initialized and stop callbacks are propagated, as they always were.
+    pub fn policy_for(&self, tp: CallbackType) -> PanicPolicy {
+        match self.policy {
+            Some(policy) => policy,
+            None => match tp {
+                CallbackType::Initialized | CallbackType::WaitForStop | CallbackType::FastStop => {
+                    PanicPolicy::Propagate
+                }
+                _ => PanicPolicy::KeepRunning,
+            },
+        }
+    }
+
+    pub fn set_hook(&mut self, hook: CallbackPanicHook) {
+        self.hook = Some(hook);
+    }
+
+    // Calls the hook, the caller acts on the returned policy.
+    pub(crate) fn caught(&self, tp: CallbackType, payload: &(dyn Any + Send)) -> PanicPolicy {
+        if let Some(hook) = &self.hook {
+            hook(tp, payload);
+        }
+
+        self.policy_for(tp)
+    }
+}
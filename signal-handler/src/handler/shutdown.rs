@@ -0,0 +1,172 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex},
+    time::Instant,
+};
+
+//
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Wakers>,
+    condvar: Condvar,
+}
+
+// Keyed by the waiting future, which removes its waker when dropped.
+#[derive(Debug, Default)]
+struct Wakers {
+    next_key: u64,
+    map: HashMap<u64, Waker>,
+}
+
+impl ShutdownToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled {
+            token: self,
+            key: None,
+        }
+    }
+
+    pub fn wait(&self) {
+        let mut wakers = self.inner.wakers.lock().expect("lock poisoned");
+        while !self.is_cancelled() {
+            wakers = self.inner.condvar.wait(wakers).expect("lock poisoned");
+        }
+    }
+
+    // Returns whether it was cancelled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+
+        let mut wakers = self.inner.wakers.lock().expect("lock poisoned");
+        while !self.is_cancelled() {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            wakers = self
+                .inner
+                .condvar
+                .wait_timeout(wakers, deadline - now)
+                .expect("lock poisoned")
+                .0;
+        }
+
+        true
+    }
+
+    pub(crate) fn cancel(&self) {
+        let wakers = {
+            let mut wakers = self.inner.wakers.lock().expect("lock poisoned");
+            if self.inner.cancelled.swap(true, Ordering::SeqCst) {
+                return;
+            }
+            self.inner.condvar.notify_all();
+            core::mem::take(&mut wakers.map)
+        };
+
+        for (_, waker) in wakers {
+            waker.wake();
+        }
+    }
+}
+
+//
+#[derive(Debug)]
+pub struct Cancelled<'a> {
+    token: &'a ShutdownToken,
+    key: Option<u64>,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let mut wakers = this.token.inner.wakers.lock().expect("lock poisoned");
+        // Checks again under the lock, cancel() may have drained the wakers in between.
+        if this.token.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let key = *this.key.get_or_insert_with(|| {
+            wakers.next_key += 1;
+            wakers.next_key
+        });
+        match wakers.map.get_mut(&key) {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            Some(waker) => *waker = cx.waker().clone(),
+            None => {
+                wakers.map.insert(key, cx.waker().clone());
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.token
+                .inner
+                .wakers
+                .lock()
+                .expect("lock poisoned")
+                .map
+                .remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures_util::FutureExt as _;
+
+    #[test]
+    fn test_cancelled() {
+        let token = ShutdownToken::new();
+
+        // A dropped future does not leave its waker behind.
+        for _ in 0..10 {
+            assert!(token.cancelled().now_or_never().is_none());
+        }
+        assert!(token.inner.wakers.lock().unwrap().map.is_empty());
+
+        let mut cancelled = token.cancelled();
+        assert!((&mut cancelled).now_or_never().is_none());
+        assert_eq!(token.inner.wakers.lock().unwrap().map.len(), 1);
+
+        token.cancel();
+        assert!(cancelled.now_or_never().is_some());
+        assert!(token.cancelled().now_or_never().is_some());
+        assert!(token.wait_timeout(Duration::from_millis(1)));
+    }
+}
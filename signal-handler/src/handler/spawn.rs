@@ -0,0 +1,84 @@
+use core::{future::Future, pin::Pin};
+use std::thread;
+
+use futures_executor::block_on;
+
+//
+pub type SpawnFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+pub type SpawnBlocking = Box<dyn FnOnce() + Send + 'static>;
+
+// The only executor specific part of `Handler::handle_async`, spawned futures are detached.
+pub trait Spawn {
+    fn spawn(&self, future: SpawnFuture);
+
+    // For the sync callbacks, each on a thread of its own.
+    // Not on a blocking pool, an abandoned callback must not hold up the runtime shutdown.
+    fn spawn_blocking(&self, f: SpawnBlocking) {
+        thread::spawn(f);
+    }
+}
+
+impl<F> Spawn for F
+where
+    F: Fn(SpawnFuture),
+{
+    fn spawn(&self, future: SpawnFuture) {
+        self(future)
+    }
+}
+
+//
+// Runs every future to completion on its own thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadSpawn;
+
+impl Spawn for ThreadSpawn {
+    fn spawn(&self, future: SpawnFuture) {
+        thread::spawn(move || block_on(future));
+    }
+}
+
+//
+#[cfg(feature = "impl_tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioSpawn;
+
+#[cfg(feature = "impl_tokio")]
+impl Spawn for TokioSpawn {
+    fn spawn(&self, future: SpawnFuture) {
+        tokio::spawn(future);
+    }
+
+    // Within the runtime, like on the runtime thread, e.g. for `tokio::spawn`.
+    fn spawn_blocking(&self, f: SpawnBlocking) {
+        let handle = tokio::runtime::Handle::try_current().ok();
+        thread::spawn(move || {
+            let _guard = handle.as_ref().map(|x| x.enter());
+            f()
+        });
+    }
+}
+
+//
+#[cfg(feature = "impl_async_std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStdSpawn;
+
+#[cfg(feature = "impl_async_std")]
+impl Spawn for AsyncStdSpawn {
+    fn spawn(&self, future: SpawnFuture) {
+        async_std::task::spawn(future);
+    }
+}
+
+//
+#[cfg(feature = "impl_smol")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmolSpawn;
+
+#[cfg(feature = "impl_smol")]
+impl Spawn for SmolSpawn {
+    fn spawn(&self, future: SpawnFuture) {
+        smol::spawn(future).detach();
+    }
+}
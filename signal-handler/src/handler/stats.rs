@@ -0,0 +1,64 @@
+use core::time::Duration;
+use std::{
+    collections::HashMap,
+    time::{Instant, SystemTime},
+};
+
+use crate::callback::CallbackType;
+
+//
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct EventStats {
+    // Signals and triggers of the type, including the coalesced and dropped ones.
+    pub received: u64,
+    pub invocations: u64,
+    pub coalesced: u64,
+    pub dropped: u64,
+    pub errors: u64,
+    pub panics: u64,
+    // For display, the monotonic instants below are compared.
+    pub last_started_at: Option<SystemTime>,
+    pub last_finished_at: Option<SystemTime>,
+    pub last_duration: Option<Duration>,
+    pub last_error: Option<String>,
+    pub total_duration: Duration,
+    pub(crate) last_started_instant: Option<Instant>,
+    pub(crate) last_finished_instant: Option<Instant>,
+}
+
+impl EventStats {
+    // Set while the callbacks of the type are running.
+    pub fn is_running(&self) -> bool {
+        match (self.last_started_instant, self.last_finished_instant) {
+            (Some(started_at), Some(finished_at)) => started_at > finished_at,
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
+}
+
+//
+// A snapshot, taken by `HandlerHandle::stats` or passed to the print_stats callbacks.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct HandlerStats {
+    // None until the handler is started.
+    pub started_at: Option<SystemTime>,
+    pub events: HashMap<CallbackType, EventStats>,
+    pub(crate) started_instant: Option<Instant>,
+}
+
+impl HandlerStats {
+    pub fn event(&self, tp: CallbackType) -> Option<&EventStats> {
+        self.events.get(&tp)
+    }
+
+    pub fn uptime(&self) -> Option<Duration> {
+        self.started_instant.map(|x| x.elapsed())
+    }
+
+    pub(crate) fn entry(&mut self, tp: CallbackType) -> &mut EventStats {
+        self.events.entry(tp).or_default()
+    }
+}
@@ -0,0 +1,92 @@
+use std::{process, sync::Arc};
+
+use signal_hook::{consts::signal::SIGTERM, low_level::emulate_default_handler};
+
+use crate::{handler::HandleError, register::SignalNumber};
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum StopStep {
+    // Runs the `wait_for_stop` callback.
+    Graceful,
+    // Abandons the running stop callback and runs the `fast_stop` callback.
+    Fast,
+    // Restores the default disposition of the stop signal and raises it again.
+    Force,
+}
+
+//
+pub type StopEscalationHook = Arc<dyn Fn(StopStep, Option<StopStep>) + Send + Sync + 'static>;
+
+#[derive(Clone, Default)]
+pub struct StopLadder {
+    steps: Vec<StopStep>,
+    hook: Option<StopEscalationHook>,
+}
+
+impl core::fmt::Debug for StopLadder {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StopLadder")
+            .field("steps", &self.steps())
+            .field("hook", &self.hook.as_ref().map(|_| "StopEscalationHook"))
+            .finish()
+    }
+}
+
+impl StopLadder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(&self) -> &[StopStep] {
+        if self.steps.is_empty() {
+            &[StopStep::Graceful]
+        } else {
+            &self.steps
+        }
+    }
+
+    pub fn set_steps<I>(&mut self, steps: I)
+    where
+        I: IntoIterator<Item = StopStep>,
+    {
+        self.steps = steps.into_iter().collect();
+    }
+
+    pub fn set_hook(&mut self, hook: StopEscalationHook) {
+        self.hook = Some(hook);
+    }
+
+    // The step for the nth (0-based) stop signal, `None` once the ladder is exhausted.
+    pub(crate) fn escalate(&self, n: usize) -> Option<StopStep> {
+        let steps = self.steps();
+        let step = steps.get(n).copied()?;
+
+        if let Some(hook) = &self.hook {
+            hook(step, steps.get(n + 1).copied());
+        }
+
+        Some(step)
+    }
+}
+
+//
+pub(crate) fn force_stop(signal_number: Option<SignalNumber>) {
+    // Terminates the process for all stop signals, returns only for signals whose default is to ignore.
+    #[allow(clippy::single_match)]
+    match emulate_default_handler(signal_number.unwrap_or(SIGTERM)) {
+        Ok(_) => {}
+        Err(_) => {
+            // Ignore, unknown signal
+        }
+    }
+}
+
+pub(crate) fn stop_timed_out(exit_code: Option<i32>) -> HandleError {
+    if let Some(code) = exit_code {
+        process::exit(code);
+    }
+
+    HandleError::StopTimedOut
+}
@@ -0,0 +1,359 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+use futures_executor::{block_on_stream, BlockingStream};
+use futures_util::{Stream, StreamExt as _};
+
+use crate::{
+    callback::{CallbackType, CoalescePolicy},
+    handler::{
+        builder::Builder,
+        impl_async::{register, RegisterReceiver},
+        timer::sleep_until_deadline,
+        HandleError, Handler, HandlerHandle,
+    },
+    register::{RegisterEvent, RegisterType, Registration, SignalNumber, SignalOrigin},
+};
+#[cfg(target_os = "linux")]
+use crate::{handler::impl_async::watch_paths_for_reload, reload::watch::FileWatcher};
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HandlerEvent {
+    ReloadConfig,
+    WaitForStop {
+        // None if triggered programmatically.
+        signal: Option<SignalNumber>,
+        origin: Option<SignalOrigin>,
+    },
+    PrintStats,
+    Custom(&'static str),
+}
+
+impl From<RegisterEvent> for HandlerEvent {
+    fn from(event: RegisterEvent) -> Self {
+        match event.tp {
+            #[cfg(not(windows))]
+            RegisterType::ReloadConfig => Self::ReloadConfig,
+            RegisterType::WaitForStop => Self::WaitForStop {
+                signal: event.signal_number,
+                origin: event.origin,
+            },
+            #[cfg(not(windows))]
+            RegisterType::PrintStats => Self::PrintStats,
+            RegisterType::Custom(name) => Self::Custom(name),
+        }
+    }
+}
+
+//
+type Sleep = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+// The callbacks of the Builder are not called, the events are handled by the consumer.
+// The coalesce policies of the Builder apply, the consumer is running an event until it polls again.
+// Dropping the stream unregisters the signals.
+pub struct HandlerStream {
+    rx: RegisterReceiver,
+    pending: VecDeque<RegisterEvent>,
+    // Held back by Debounce and Throttle, one per type.
+    delayed: Vec<(Instant, RegisterEvent)>,
+    timer: Option<(Instant, Sleep)>,
+    running: Option<RegisterType>,
+    latest_yield: HashMap<RegisterType, Instant>,
+    handle: HandlerHandle,
+    _registration: Registration,
+    #[cfg(target_os = "linux")]
+    _file_watcher: Option<FileWatcher>,
+}
+
+impl core::fmt::Debug for HandlerStream {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("HandlerStream")
+            .field("pending", &self.pending)
+            .field("delayed", &self.delayed)
+            .field("handle", &self.handle)
+            .finish_non_exhaustive()
+    }
+}
+
+impl HandlerStream {
+    // Like `Builder::coalesce`. Stop events are never coalesced, each of them may escalate.
+    pub fn coalesce(self, tp: CallbackType, coalesce: CoalescePolicy) -> Self {
+        self.handle.set_coalesce(tp, coalesce);
+
+        self
+    }
+
+    pub fn control_handle(&self) -> HandlerHandle {
+        self.handle.clone()
+    }
+
+    pub fn into_blocking_iter(self) -> HandlerIter {
+        HandlerIter(block_on_stream(self))
+    }
+
+    // `running` is the type the consumer was handling while the event was received.
+    fn push(&mut self, event: RegisterEvent, running: Option<RegisterType>, now: Instant) {
+        if event.tp == RegisterType::WaitForStop {
+            self.pending.push_back(event);
+            return;
+        }
+
+        match self.handle.coalesce(&CallbackType::from(event.tp)) {
+            CoalescePolicy::CoalesceWhileRunning => self.fold_or_push(event),
+            CoalescePolicy::Queue => self.pending.push_back(event),
+            CoalescePolicy::DropWhileRunning => {
+                if running != Some(event.tp) {
+                    self.fold_or_push(event);
+                }
+            }
+            CoalescePolicy::Debounce(quiet) => self.delay(event, now + quiet),
+            CoalescePolicy::Throttle(interval) => {
+                let latest_yield = self.latest_yield.get(&event.tp).copied();
+
+                match latest_yield.map(|x| x + interval).filter(|x| *x > now) {
+                    Some(deadline) => self.delay(event, deadline),
+                    None if self.delayed.iter().any(|(_, x)| x.tp == event.tp) => {
+                        self.delay(event, now)
+                    }
+                    None => self.fold_or_push(event),
+                }
+            }
+        }
+    }
+
+    fn fold_or_push(&mut self, event: RegisterEvent) {
+        match self.pending.iter_mut().find(|x| x.tp == event.tp) {
+            Some(pending) => *pending = event,
+            None => self.pending.push_back(event),
+        }
+    }
+
+    // A delayed event of the type is replaced, keeping the earlier deadline unless debouncing.
+    fn delay(&mut self, event: RegisterEvent, deadline: Instant) {
+        let debounce = matches!(
+            self.handle.coalesce(&CallbackType::from(event.tp)),
+            CoalescePolicy::Debounce(_)
+        );
+
+        match self.delayed.iter_mut().find(|(_, x)| x.tp == event.tp) {
+            Some((x, delayed)) => {
+                if debounce {
+                    *x = deadline;
+                }
+                *delayed = event;
+            }
+            None => self.delayed.push((deadline, event)),
+        }
+    }
+
+    fn release_due(&mut self, now: Instant) {
+        let mut i = 0;
+        while i < self.delayed.len() {
+            if self.delayed[i].0 <= now {
+                let (_, event) = self.delayed.remove(i);
+                self.fold_or_push(event);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+impl Stream for HandlerStream {
+    type Item = HandlerEvent;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        // The events queued since the previous yield were received while it was handled.
+        let running = this.running.take();
+
+        loop {
+            let now = Instant::now();
+
+            while let Poll::Ready(Some(event)) = this.rx.poll_next_unpin(cx) {
+                this.push(event, running, now);
+            }
+
+            this.release_due(now);
+
+            if let Some(event) = this.pending.pop_front() {
+                if event.tp == RegisterType::WaitForStop {
+                    this.handle.shutdown_token().cancel();
+                }
+                this.running = Some(event.tp);
+                this.latest_yield.insert(event.tp, now);
+
+                return Poll::Ready(Some(event.into()));
+            }
+
+            let deadline = match this.delayed.iter().map(|(x, _)| *x).min() {
+                Some(deadline) => deadline,
+                None => return Poll::Pending,
+            };
+
+            if this.timer.as_ref().map(|(x, _)| *x) != Some(deadline) {
+                this.timer = Some((deadline, Box::pin(sleep_until_deadline(Some(deadline)))));
+            }
+            match this.timer.as_mut().map(|(_, x)| x.as_mut().poll(cx)) {
+                Some(Poll::Ready(())) => this.timer = None,
+                _ => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Drop for HandlerStream {
+    fn drop(&mut self) {
+        self.handle.set_sender(None);
+        self.handle.shutdown_token().cancel();
+    }
+}
+
+//
+#[derive(Debug)]
+pub struct HandlerIter(BlockingStream<HandlerStream>);
+
+impl HandlerIter {
+    pub fn control_handle(&self) -> HandlerHandle {
+        self.0.control_handle()
+    }
+}
+
+impl Iterator for HandlerIter {
+    type Item = HandlerEvent;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+//
+impl Handler {
+    pub fn into_stream(self) -> Result<HandlerStream, HandleError> {
+        let Builder {
+            registers,
+            restore_default_on_exit,
+            #[cfg(target_os = "linux")]
+            watch_paths,
+            ..
+        } = self.builder;
+        let handle = self.handle;
+
+        let (register_tx, register_rx, registration) =
+            register(registers, restore_default_on_exit)?;
+
+        #[cfg(target_os = "linux")]
+        let file_watcher = watch_paths_for_reload(&watch_paths, register_tx.clone())?;
+
+        handle.set_sender(Some(Box::new(register_tx)));
+
+        Ok(HandlerStream {
+            rx: register_rx,
+            pending: VecDeque::new(),
+            delayed: vec![],
+            timer: None,
+            running: None,
+            latest_yield: HashMap::new(),
+            handle,
+            _registration: registration,
+            #[cfg(target_os = "linux")]
+            _file_watcher: file_watcher,
+        })
+    }
+
+    pub fn into_blocking_iter(self) -> Result<HandlerIter, HandleError> {
+        self.into_stream().map(HandlerStream::into_blocking_iter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{thread, time::Duration};
+
+    use futures_util::task::noop_waker_ref;
+
+    // A burst of 4 reloads, with `while_running` the last 3 are sent while the first one is handled.
+    // Returns when each reload was yielded, the others of the burst were coalesced or dropped.
+    fn burst(coalesce: CoalescePolicy, while_running: bool) -> Vec<Instant> {
+        let mut stream = Handler::builder()
+            .build()
+            .into_stream()
+            .unwrap()
+            .coalesce(CallbackType::ReloadConfig, coalesce);
+        let handle = stream.control_handle();
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        let mut yielded = vec![];
+        if while_running {
+            handle.trigger(RegisterType::ReloadConfig).unwrap();
+            assert_eq!(
+                stream.poll_next_unpin(&mut cx),
+                Poll::Ready(Some(HandlerEvent::ReloadConfig))
+            );
+            yielded.push(Instant::now());
+        }
+        for _ in yielded.len()..4 {
+            handle.trigger(RegisterType::ReloadConfig).unwrap();
+        }
+
+        // Long enough for the debounce and the throttle.
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            match stream.poll_next_unpin(&mut cx) {
+                Poll::Ready(Some(event)) => {
+                    assert_eq!(event, HandlerEvent::ReloadConfig);
+                    yielded.push(Instant::now());
+                }
+                Poll::Ready(None) => unreachable!(),
+                Poll::Pending => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+
+        yielded
+    }
+
+    #[test]
+    fn test_coalesce_while_running() {
+        assert_eq!(burst(CoalescePolicy::CoalesceWhileRunning, true).len(), 2);
+    }
+
+    #[test]
+    fn test_coalesce_queue() {
+        assert_eq!(burst(CoalescePolicy::Queue, true).len(), 4);
+    }
+
+    #[test]
+    fn test_coalesce_drop_while_running() {
+        assert_eq!(burst(CoalescePolicy::DropWhileRunning, true).len(), 1);
+    }
+
+    #[test]
+    fn test_coalesce_debounce() {
+        let quiet = Duration::from_millis(100);
+        let start = Instant::now();
+        let yielded = burst(CoalescePolicy::Debounce(quiet), false);
+        assert_eq!(yielded.len(), 1);
+        assert!(yielded[0] - start >= quiet);
+    }
+
+    #[test]
+    fn test_coalesce_throttle() {
+        let interval = Duration::from_millis(200);
+        let start = Instant::now();
+        let yielded = burst(CoalescePolicy::Throttle(interval), true);
+        assert_eq!(yielded.len(), 2);
+        assert!(yielded[1] - start >= interval);
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Mutex, OnceLock,
+    },
+    thread,
+    time::Instant,
+};
+
+use futures_channel::oneshot;
+use futures_util::{
+    future::{self, FusedFuture},
+    FutureExt as _,
+};
+
+//
+type Entry = (Instant, oneshot::Sender<()>);
+
+static TIMER: OnceLock<Mutex<mpsc::Sender<Entry>>> = OnceLock::new();
+
+// Timers are executor specific, so the deadlines are waited for on a thread,
+// a single one shared by all the handlers. Dropping the future cancels it.
+pub(crate) fn sleep_until_deadline(deadline: Option<Instant>) -> impl FusedFuture<Output = ()> {
+    let rx = deadline.map(|deadline| {
+        let (tx, rx) = oneshot::channel::<()>();
+        let timer = TIMER.get_or_init(|| {
+            let (entry_tx, entry_rx) = mpsc::channel();
+            thread::spawn(move || run(entry_rx));
+            Mutex::new(entry_tx)
+        });
+        // The thread never exits while the sender is alive.
+        let _ = timer.lock().expect("lock poisoned").send((deadline, tx));
+        rx
+    });
+
+    async move {
+        match rx {
+            Some(rx) => {
+                let _ = rx.await;
+            }
+            None => future::pending().await,
+        }
+    }
+    .fuse()
+}
+
+fn run(rx: mpsc::Receiver<Entry>) {
+    let mut entries: Vec<Entry> = vec![];
+
+    loop {
+        // The futures dropped before their deadline.
+        entries.retain(|(_, tx)| !tx.is_canceled());
+
+        let ret = match entries.iter().map(|(deadline, _)| *deadline).min() {
+            Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match ret {
+            Ok(entry) => entries.push(entry),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        let mut i = 0;
+        while i < entries.len() {
+            if entries[i].0 <= now {
+                let (_, tx) = entries.swap_remove(i);
+                let _ = tx.send(());
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
@@ -4,5 +4,9 @@ pub use signal_hook::consts::signal::*;
 pub mod callback;
 pub mod handler;
 pub mod register;
+pub mod reload;
+pub mod stats;
+#[cfg(feature = "systemd")]
+pub mod systemd;
 
 pub use handler::Handler;
@@ -1,24 +1,39 @@
-use core::ops::{Deref, DerefMut};
-use std::{collections::HashMap, io::Error as IoError};
+use core::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+    task::{Context, Poll},
+};
+use std::{
+    collections::HashMap,
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    sync::Arc,
+};
 
 use channel_sender::{generic::Sender, SendError};
+use futures_util::task::AtomicWaker;
+#[cfg(all(feature = "siginfo", not(windows)))]
+use signal_hook::low_level::siginfo::{Cause, Origin, Sent};
 use signal_hook::{
-    consts::signal::*,
-    low_level::{register, unregister},
+    consts::{signal::*, FORBIDDEN},
+    low_level::{emulate_default_handler, register, unregister},
     SigId,
 };
+#[cfg(all(feature = "siginfo", not(windows)))]
+use signal_hook_registry::register_sigaction;
 
 //
 pub type SignalNumber = i32;
 
 //
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum RegisterType {
     #[cfg(not(windows))]
     ReloadConfig,
     WaitForStop,
     #[cfg(not(windows))]
     PrintStats,
+    Custom(&'static str),
 }
 
 impl RegisterType {
@@ -40,6 +55,87 @@ impl RegisterType {
             RegisterType::PrintStats => {
                 vec![SIGUSR1]
             }
+            RegisterType::Custom(_) => {
+                // Custom types have no default, the signal numbers come from the user.
+                vec![]
+            }
+        }
+    }
+}
+
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RegisterEvent {
+    pub tp: RegisterType,
+    // None if triggered programmatically.
+    pub signal_number: Option<SignalNumber>,
+    pub origin: Option<SignalOrigin>,
+}
+
+impl RegisterEvent {
+    pub fn new(tp: RegisterType) -> Self {
+        Self {
+            tp,
+            signal_number: None,
+            origin: None,
+        }
+    }
+
+    pub fn with_signal_number(mut self, signal_number: SignalNumber) -> Self {
+        self.signal_number = Some(signal_number);
+        self
+    }
+
+    pub fn with_origin(mut self, origin: SignalOrigin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+}
+
+//
+// Who sent the signal, only available with the `siginfo` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SignalOrigin {
+    pub pid: Option<i32>,
+    pub uid: Option<u32>,
+    pub cause: SignalCause,
+}
+
+// The si_code of the signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SignalCause {
+    Unknown,
+    Kernel,
+    // kill
+    User,
+    // tkill
+    TKill,
+    // sigqueue
+    Queue,
+    // mq_notify
+    MesgQ,
+    // SIGCHLD
+    Child,
+}
+
+#[cfg(all(feature = "siginfo", not(windows)))]
+impl From<Origin> for SignalOrigin {
+    fn from(origin: Origin) -> Self {
+        Self {
+            pid: origin.process.map(|x| x.pid),
+            uid: origin.process.map(|x| x.uid),
+            cause: match origin.cause {
+                Cause::Kernel => SignalCause::Kernel,
+                Cause::Sent(Sent::User) => SignalCause::User,
+                Cause::Sent(Sent::TKill) => SignalCause::TKill,
+                Cause::Sent(Sent::Queue) => SignalCause::Queue,
+                Cause::Sent(Sent::MesgQ) => SignalCause::MesgQ,
+                Cause::Chld(_) => SignalCause::Child,
+                _ => SignalCause::Unknown,
+            },
         }
     }
 }
@@ -90,17 +186,92 @@ impl Registers {
             RegisterType::PrintStats.signal_numbers(),
         )
     }
+
+    pub fn insert_custom(
+        &mut self,
+        name: &'static str,
+        signal_numbers: Vec<SignalNumber>,
+    ) -> Option<Vec<SignalNumber>> {
+        self.insert(RegisterType::Custom(name), signal_numbers)
+    }
+
+    pub fn get_or_insert_default(&mut self, tp: RegisterType) -> &mut Vec<SignalNumber> {
+        self.entry(tp).or_insert_with(|| tp.signal_numbers())
+    }
+
+    pub fn set_signal_numbers<I>(
+        &mut self,
+        tp: RegisterType,
+        signal_numbers: I,
+    ) -> Option<Vec<SignalNumber>>
+    where
+        I: IntoIterator<Item = SignalNumber>,
+    {
+        let mut list = vec![];
+        push_unique(&mut list, signal_numbers);
+        self.insert(tp, list)
+    }
+
+    pub fn extend_signal_numbers<I>(&mut self, tp: RegisterType, signal_numbers: I)
+    where
+        I: IntoIterator<Item = SignalNumber>,
+    {
+        push_unique(self.get_or_insert_default(tp), signal_numbers)
+    }
+
+    pub fn validate(&self) -> Result<(), RegisterError> {
+        let mut claimed = HashMap::<SignalNumber, RegisterType>::new();
+
+        for (tp, signal_numbers) in &self.0 {
+            for signal_number in signal_numbers {
+                if FORBIDDEN.contains(signal_number) {
+                    return Err(RegisterError::new(
+                        IoErrorKind::InvalidInput,
+                        format!("signal {} cannot be registered", signal_number),
+                    ));
+                }
+
+                if let Some(other_tp) = claimed.insert(*signal_number, *tp) {
+                    if other_tp != *tp {
+                        return Err(RegisterError::new(
+                            IoErrorKind::InvalidInput,
+                            format!(
+                                "signal {} is claimed by both {:?} and {:?}",
+                                signal_number, other_tp, tp
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn push_unique<I>(list: &mut Vec<SignalNumber>, signal_numbers: I)
+where
+    I: IntoIterator<Item = SignalNumber>,
+{
+    for signal_number in signal_numbers {
+        if !list.contains(&signal_number) {
+            list.push(signal_number);
+        }
+    }
 }
 
 //
 pub type RegisterError = IoError;
 
 impl Registers {
-    pub fn register<Tx>(self, sender: Tx) -> Result<HashMap<SignalNumber, SigId>, RegisterError>
+    pub fn register<Tx>(self, sender: Tx) -> Result<Registration, RegisterError>
     where
-        Tx: Sender<RegisterType> + Clone + Send + Sync + 'static,
+        Tx: Sender<RegisterEvent> + Clone + Send + Sync + 'static,
     {
-        let mut map = HashMap::new();
+        self.validate()?;
+
+        // Unregisters the already registered ones on failure.
+        let mut registration = Registration::default();
 
         for (tp, signal_numbers) in &self.0 {
             for signal_number in signal_numbers {
@@ -109,25 +280,33 @@ impl Registers {
                 let signal_number = *signal_number;
                 let tp = *tp;
 
+                #[cfg(all(feature = "siginfo", not(windows)))]
+                let sig_id = unsafe {
+                    register_sigaction(signal_number, move |info| {
+                        let origin = Origin::extract(info);
+                        send(
+                            &sender,
+                            RegisterEvent::new(tp)
+                                .with_signal_number(signal_number)
+                                .with_origin(origin.into()),
+                        )
+                    })
+                }?;
+                #[cfg(not(all(feature = "siginfo", not(windows))))]
                 let sig_id = unsafe {
                     register(signal_number, move || {
-                        match sender.send(tp) {
-                            Ok(_) => {}
-                            Err(SendError::Full(_)) => {
-                                // ignore
-                            }
-                            Err(SendError::Closed(_)) | Err(SendError::Disconnected(_)) => {
-                                // ignore
-                            }
-                        }
+                        send(
+                            &sender,
+                            RegisterEvent::new(tp).with_signal_number(signal_number),
+                        )
                     })
                 }?;
 
-                map.insert(signal_number, sig_id);
+                registration.sig_id_map.insert(signal_number, sig_id);
             }
         }
 
-        Ok(map)
+        Ok(registration)
     }
 
     pub fn unregister(sig_ids: &[SigId]) {
@@ -136,3 +315,265 @@ impl Registers {
         }
     }
 }
+
+fn send<Tx>(sender: &Tx, event: RegisterEvent)
+where
+    Tx: Sender<RegisterEvent>,
+{
+    match sender.send(event) {
+        Ok(_) => {}
+        Err(SendError::Full(_)) => {
+            // ignore
+        }
+        Err(SendError::Closed(_)) | Err(SendError::Disconnected(_)) => {
+            // ignore
+        }
+    }
+}
+
+//
+// At most this many signals of a signal number wait to be handled, later ones are dropped.
+const MAX_PENDING: usize = 6;
+
+// The signals received and not handled yet, one slot per signal number.
+// Written by the signal handlers, so without allocating nor locking,
+// the events are created by the task taking them.
+#[derive(Debug)]
+pub(crate) struct PendingSignals {
+    slots: Vec<PendingSlot>,
+    waker: AtomicWaker,
+}
+
+#[derive(Debug)]
+struct PendingSlot {
+    tp: RegisterType,
+    signal_number: SignalNumber,
+    count: AtomicUsize,
+    origin: AtomicOrigin,
+}
+
+impl PendingSignals {
+    pub(crate) fn new(registers: &Registers) -> Arc<Self> {
+        let slots = registers
+            .iter()
+            .flat_map(|(tp, signal_numbers)| {
+                signal_numbers.iter().map(|signal_number| PendingSlot {
+                    tp: *tp,
+                    signal_number: *signal_number,
+                    count: AtomicUsize::new(0),
+                    origin: AtomicOrigin::default(),
+                })
+            })
+            .collect();
+
+        Arc::new(Self {
+            slots,
+            waker: AtomicWaker::new(),
+        })
+    }
+
+    pub(crate) fn poll_take(&self, cx: &mut Context<'_>) -> Poll<RegisterEvent> {
+        self.waker.register(cx.waker());
+
+        for slot in &self.slots {
+            if slot
+                .count
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| x.checked_sub(1))
+                .is_ok()
+            {
+                let mut event = RegisterEvent::new(slot.tp).with_signal_number(slot.signal_number);
+                event.origin = slot.origin.load();
+
+                return Poll::Ready(event);
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct PendingSender(pub(crate) Arc<PendingSignals>);
+
+impl Sender<RegisterEvent> for PendingSender {
+    fn send(&self, t: RegisterEvent) -> Result<(), SendError<RegisterEvent>> {
+        let slot = match self
+            .0
+            .slots
+            .iter()
+            .find(|x| Some(x.signal_number) == t.signal_number)
+        {
+            Some(slot) => slot,
+            None => return Err(SendError::Closed(t)),
+        };
+
+        // Stored first, the taking task may see the count right after it was increased.
+        slot.origin.store(t.origin);
+        if slot
+            .count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                (x < MAX_PENDING).then_some(x + 1)
+            })
+            .is_err()
+        {
+            return Err(SendError::Full(t));
+        }
+
+        self.0.waker.wake();
+
+        Ok(())
+    }
+}
+
+// The origin of the latest signal of the slot.
+// Two signals racing may mix their origins, which is acceptable for a diagnostic.
+#[derive(Debug, Default)]
+struct AtomicOrigin {
+    // 0 without an origin, otherwise the cause plus one, with PROCESS_BIT if there is a process.
+    meta: AtomicU8,
+    // The pid in the high half, the uid in the low half.
+    process: AtomicU64,
+}
+
+const PROCESS_BIT: u8 = 0x80;
+
+impl AtomicOrigin {
+    fn store(&self, origin: Option<SignalOrigin>) {
+        let origin = match origin {
+            Some(origin) => origin,
+            None => {
+                self.meta.store(0, Ordering::SeqCst);
+                return;
+            }
+        };
+
+        let mut meta = cause_to_u8(origin.cause) + 1;
+        if let (Some(pid), Some(uid)) = (origin.pid, origin.uid) {
+            self.process
+                .store(((pid as u32 as u64) << 32) | uid as u64, Ordering::SeqCst);
+            meta |= PROCESS_BIT;
+        }
+        self.meta.store(meta, Ordering::SeqCst);
+    }
+
+    fn load(&self) -> Option<SignalOrigin> {
+        let meta = self.meta.load(Ordering::SeqCst);
+        if meta == 0 {
+            return None;
+        }
+
+        let (pid, uid) = if meta & PROCESS_BIT != 0 {
+            let process = self.process.load(Ordering::SeqCst);
+            (Some((process >> 32) as u32 as i32), Some(process as u32))
+        } else {
+            (None, None)
+        };
+
+        Some(SignalOrigin {
+            pid,
+            uid,
+            cause: cause_from_u8((meta & !PROCESS_BIT) - 1),
+        })
+    }
+}
+
+fn cause_to_u8(cause: SignalCause) -> u8 {
+    match cause {
+        SignalCause::Unknown => 0,
+        SignalCause::Kernel => 1,
+        SignalCause::User => 2,
+        SignalCause::TKill => 3,
+        SignalCause::Queue => 4,
+        SignalCause::MesgQ => 5,
+        SignalCause::Child => 6,
+    }
+}
+
+fn cause_from_u8(n: u8) -> SignalCause {
+    match n {
+        1 => SignalCause::Kernel,
+        2 => SignalCause::User,
+        3 => SignalCause::TKill,
+        4 => SignalCause::Queue,
+        5 => SignalCause::MesgQ,
+        6 => SignalCause::Child,
+        _ => SignalCause::Unknown,
+    }
+}
+
+//
+#[derive(Debug, Default)]
+pub struct Registration {
+    sig_id_map: HashMap<SignalNumber, SigId>,
+    restore_default: bool,
+}
+
+impl Registration {
+    pub fn sig_id_map(&self) -> &HashMap<SignalNumber, SigId> {
+        &self.sig_id_map
+    }
+
+    // Once dropped, the signals behave as if they were never handled, e.g. SIGINT terminates the process.
+    // Note: this is permanent for the process, later registrations of the same signals still get terminated.
+    pub fn set_restore_default(&mut self, restore_default: bool) {
+        self.restore_default = restore_default;
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        for (signal_number, sig_id) in self.sig_id_map.drain() {
+            unregister(sig_id);
+
+            if self.restore_default {
+                #[allow(clippy::single_match)]
+                match unsafe {
+                    register(signal_number, move || {
+                        let _ = emulate_default_handler(signal_number);
+                    })
+                } {
+                    Ok(_) => {}
+                    Err(_) => {
+                        // Ignore, cannot fail for a signal registered before
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_registers_validate() {
+        let mut registers = Registers::new();
+        registers.insert_wait_for_stop();
+        registers.insert_reload_config();
+        registers.insert_print_stats();
+        registers.insert_custom("rotate", vec![SIGUSR2]);
+        assert!(registers.validate().is_ok());
+
+        // The same signal twice for the same type.
+        registers.extend_signal_numbers(RegisterType::Custom("rotate"), [SIGUSR2]);
+        assert_eq!(registers[&RegisterType::Custom("rotate")], vec![SIGUSR2]);
+        assert!(registers.validate().is_ok());
+
+        // Claimed by another type.
+        registers.extend_signal_numbers(RegisterType::Custom("dump"), [SIGHUP]);
+        let err = registers.validate().unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidInput);
+        assert!(err.to_string().contains("is claimed by both"));
+
+        registers.remove(&RegisterType::Custom("dump"));
+        assert!(registers.validate().is_ok());
+
+        // Forbidden, e.g. SIGKILL.
+        registers.set_signal_numbers(RegisterType::Custom("kill"), [SIGKILL]);
+        let err = registers.validate().unwrap_err();
+        assert_eq!(err.kind(), IoErrorKind::InvalidInput);
+        assert!(err.to_string().contains("cannot be registered"));
+    }
+}
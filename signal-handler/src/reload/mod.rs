@@ -0,0 +1,277 @@
+use std::{
+    fs,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+use crate::callback::CallbackError;
+
+#[cfg(target_os = "linux")]
+pub mod watch;
+
+//
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReloadError {
+    Io(IoError),
+    Parse(CallbackError),
+    Validate(CallbackError),
+}
+
+impl core::fmt::Display for ReloadError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) | Self::Validate(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+//
+type Parser<T> = Box<dyn Fn(&[u8]) -> Result<T, CallbackError> + Send + Sync + 'static>;
+type Validator<T> = Box<dyn Fn(&T) -> Result<(), CallbackError> + Send + Sync + 'static>;
+type ChangeHook<T> = Box<dyn Fn(&T, &T) + Send + Sync + 'static>;
+
+struct Loader<T> {
+    path: PathBuf,
+    parser: Parser<T>,
+    validator: Option<Validator<T>>,
+}
+
+impl<T> Loader<T> {
+    fn load(&self) -> Result<T, ReloadError> {
+        let bytes = fs::read(&self.path).map_err(ReloadError::Io)?;
+        let value = (self.parser)(&bytes).map_err(ReloadError::Parse)?;
+
+        if let Some(validator) = &self.validator {
+            validator(&value).map_err(ReloadError::Validate)?;
+        }
+
+        Ok(value)
+    }
+}
+
+//
+pub struct ConfigReloaderBuilder<T> {
+    loader: Loader<T>,
+    on_change: Option<ChangeHook<T>>,
+}
+
+impl<T> core::fmt::Debug for ConfigReloaderBuilder<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ConfigReloaderBuilder")
+            .field("path", &self.loader.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> ConfigReloaderBuilder<T>
+where
+    T: Send + Sync + 'static,
+{
+    // Rejected values are not published, the readers keep the previous one.
+    pub fn validate<F, E>(mut self, f: F) -> Self
+    where
+        F: Fn(&T) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        self.loader.validator = Some(Box::new(move |x| f(x).map_err(Into::into)));
+
+        self
+    }
+
+    // Called with the old and the new value after each successful reload.
+    pub fn on_change<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&T, &T) + Send + Sync + 'static,
+    {
+        self.on_change = Some(Box::new(f));
+
+        self
+    }
+
+    // The initial load must succeed, there is no previous value to fall back to.
+    pub fn build(self) -> Result<ConfigReloader<T>, ReloadError> {
+        let value = self.loader.load()?;
+
+        Ok(ConfigReloader {
+            inner: Arc::new(Inner {
+                loader: self.loader,
+                on_change: self.on_change,
+                current: RwLock::new(Arc::new(value)),
+                reloading: Mutex::new(()),
+            }),
+        })
+    }
+}
+
+//
+struct Inner<T> {
+    loader: Loader<T>,
+    on_change: Option<ChangeHook<T>>,
+    current: RwLock<Arc<T>>,
+    // Reloads are serialized, so that on_change sees every transition once.
+    reloading: Mutex<()>,
+}
+
+pub struct ConfigReloader<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Clone for ConfigReloader<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for ConfigReloader<T>
+where
+    T: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ConfigReloader")
+            .field("path", &self.inner.loader.path)
+            .field(
+                "current",
+                &*self.inner.current.read().expect("lock poisoned"),
+            )
+            .finish()
+    }
+}
+
+impl<T> ConfigReloader<T>
+where
+    T: Send + Sync + 'static,
+{
+    pub fn builder<P, F, E>(path: P, parser: F) -> ConfigReloaderBuilder<T>
+    where
+        P: AsRef<Path>,
+        F: Fn(&[u8]) -> Result<T, E> + Send + Sync + 'static,
+        E: Into<CallbackError>,
+    {
+        ConfigReloaderBuilder {
+            loader: Loader {
+                path: path.as_ref().to_owned(),
+                parser: Box::new(move |bytes| parser(bytes).map_err(Into::into)),
+                validator: None,
+            },
+            on_change: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.inner.loader.path
+    }
+
+    // A snapshot, unaffected by later reloads.
+    pub fn load(&self) -> Arc<T> {
+        self.inner.current.read().expect("lock poisoned").clone()
+    }
+
+    // Keeps the current value on failure.
+    pub fn reload(&self) -> Result<(), ReloadError> {
+        let _reloading = self.inner.reloading.lock().expect("lock poisoned");
+
+        let new = Arc::new(self.inner.loader.load()?);
+        let old = core::mem::replace(
+            &mut *self.inner.current.write().expect("lock poisoned"),
+            new.clone(),
+        );
+
+        if let Some(on_change) = &self.inner.on_change {
+            on_change(&old, &new);
+        }
+
+        Ok(())
+    }
+}
+
+//
+#[cfg(feature = "reload_json")]
+pub fn json<T>(bytes: &[u8]) -> Result<T, CallbackError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    serde_json::from_slice(bytes).map_err(Into::into)
+}
+
+#[cfg(feature = "reload_toml")]
+pub fn toml<T>(bytes: &[u8]) -> Result<T, CallbackError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let s = core::str::from_utf8(bytes)?;
+
+    ::toml::from_str(s).map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(bytes: &[u8]) -> Result<u32, CallbackError> {
+        Ok(core::str::from_utf8(bytes)?.trim().parse()?)
+    }
+
+    #[test]
+    fn test_reload() {
+        let path =
+            std::env::temp_dir().join(format!("signal-handler-reload-{}.conf", std::process::id()));
+        fs::write(&path, "1").unwrap();
+
+        let changes = Arc::new(Mutex::new(vec![]));
+        let changes_inner = changes.clone();
+        let reloader = ConfigReloader::builder(&path, parse)
+            .validate(|x| {
+                if *x > 0 {
+                    Ok(())
+                } else {
+                    Err("must be positive")
+                }
+            })
+            .on_change(move |old, new| changes_inner.lock().unwrap().push((*old, *new)))
+            .build()
+            .unwrap();
+        assert_eq!(*reloader.load(), 1);
+
+        let snapshot = reloader.load();
+        fs::write(&path, "2").unwrap();
+        reloader.reload().unwrap();
+        assert_eq!(*reloader.load(), 2);
+        assert_eq!(*snapshot, 1);
+
+        // The old value is kept on failure.
+        fs::write(&path, "x").unwrap();
+        assert!(matches!(reloader.reload(), Err(ReloadError::Parse(_))));
+        assert_eq!(*reloader.load(), 2);
+
+        fs::write(&path, "0").unwrap();
+        assert!(matches!(reloader.reload(), Err(ReloadError::Validate(_))));
+        assert_eq!(*reloader.load(), 2);
+
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(reloader.reload(), Err(ReloadError::Io(_))));
+        assert_eq!(*reloader.load(), 2);
+
+        assert_eq!(*changes.lock().unwrap(), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_build_failed() {
+        let path = std::env::temp_dir().join("signal-handler-reload-missing.conf");
+
+        assert!(matches!(
+            ConfigReloader::builder(&path, parse).build(),
+            Err(ReloadError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,351 @@
+use core::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use std::{
+    collections::HashMap,
+    ffi::CString,
+    fs,
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    os::unix::{ffi::OsStrExt as _, fs::MetadataExt as _},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+//
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+const WATCH_MASK: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_MOVED_TO
+    | libc::IN_MOVED_FROM
+    | libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_ATTRIB;
+
+//
+// Watches files and directories with inotify, calls `on_change` after each batch of changes.
+// A file is watched through its parent directory, and through the directory of its symlink
+// target, so that files replaced by rename or by an atomic symlink swap are followed,
+// e.g. the `..data` symlink of a Kubernetes ConfigMap volume.
+// Stopped on drop, or once `on_change` returns false.
+#[derive(Debug)]
+pub struct FileWatcher {
+    stop: Arc<AtomicBool>,
+}
+
+impl FileWatcher {
+    pub fn new<I, P, F>(paths: I, mut on_change: F) -> Result<Self, IoError>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+        F: FnMut() -> bool + Send + 'static,
+    {
+        let mut inotify = Inotify::new()?;
+
+        let mut targets = vec![];
+        for path in paths {
+            let path = path.as_ref().to_owned();
+            let target = if fs::metadata(&path)?.is_dir() {
+                inotify.add_watch(&path)?;
+                Target::Dir(path)
+            } else {
+                let target = Target::File {
+                    fingerprint: fingerprint(&path),
+                    path,
+                };
+                inotify.add_file_watches(&target)?;
+                target
+            };
+            targets.push(target);
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let stop_inner = stop.clone();
+        thread::spawn(move || {
+            while !stop_inner.load(Ordering::SeqCst) {
+                let wds = match inotify.read_events(POLL_INTERVAL) {
+                    Ok(wds) => wds,
+                    Err(_) => break,
+                };
+                if wds.is_empty() {
+                    continue;
+                }
+
+                let mut changed = false;
+                for target in targets.iter_mut() {
+                    match target {
+                        Target::Dir(path) => {
+                            changed |= wds.iter().any(|wd| inotify.path(*wd) == Some(&*path));
+                        }
+                        Target::File { .. } => {
+                            changed |= target.refresh();
+
+                            // The symlink target may have moved to a new directory.
+                            let _ = inotify.add_file_watches(target);
+                        }
+                    }
+                }
+
+                if changed && !on_change() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { stop })
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+//
+enum Target {
+    Dir(PathBuf),
+    File {
+        path: PathBuf,
+        fingerprint: Option<Fingerprint>,
+    },
+}
+
+impl Target {
+    // Whether the file changed since the last call, a removed file is not a change.
+    fn refresh(&mut self) -> bool {
+        match self {
+            Self::Dir(_) => false,
+            Self::File { path, fingerprint } => match self::fingerprint(path) {
+                Some(x) if Some(x) != *fingerprint => {
+                    *fingerprint = Some(x);
+                    true
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+// Of the file the path resolves to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime: i64,
+    mtime_nsec: i64,
+}
+
+fn fingerprint(path: &Path) -> Option<Fingerprint> {
+    fs::metadata(path).ok().map(|x| Fingerprint {
+        dev: x.dev(),
+        ino: x.ino(),
+        size: x.size(),
+        mtime: x.mtime(),
+        mtime_nsec: x.mtime_nsec(),
+    })
+}
+
+//
+struct Inotify {
+    fd: libc::c_int,
+    watches: HashMap<libc::c_int, PathBuf>,
+}
+
+impl Inotify {
+    fn new() -> Result<Self, IoError> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(IoError::last_os_error());
+        }
+
+        Ok(Self {
+            fd,
+            watches: HashMap::new(),
+        })
+    }
+
+    fn add_watch(&mut self, path: &Path) -> Result<(), IoError> {
+        if self.watches.values().any(|x| x == path) {
+            return Ok(());
+        }
+
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| IoError::new(IoErrorKind::InvalidInput, err))?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd, c_path.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            return Err(IoError::last_os_error());
+        }
+        self.watches.insert(wd, path.to_owned());
+
+        Ok(())
+    }
+
+    fn add_file_watches(&mut self, target: &Target) -> Result<(), IoError> {
+        let path = match target {
+            Target::File { path, .. } => path,
+            Target::Dir(_) => return Ok(()),
+        };
+
+        self.add_watch(parent(path))?;
+        if let Ok(canonical_path) = fs::canonicalize(path) {
+            self.add_watch(parent(&canonical_path))?;
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, wd: libc::c_int) -> Option<&PathBuf> {
+        self.watches.get(&wd)
+    }
+
+    // The watch descriptors with events, empty if none arrived within the timeout.
+    fn read_events(&mut self, timeout: Duration) -> Result<Vec<libc::c_int>, IoError> {
+        let mut pollfd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let n = unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) };
+        if n < 0 {
+            let err = IoError::last_os_error();
+            return match err.kind() {
+                IoErrorKind::Interrupted => Ok(vec![]),
+                _ => Err(err),
+            };
+        }
+
+        let mut wds = vec![];
+        let mut buf = [0_u8; 4096];
+        loop {
+            let n =
+                unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            if n <= 0 {
+                break;
+            }
+
+            let mut offset = 0;
+            while offset + core::mem::size_of::<libc::inotify_event>() <= n as usize {
+                let event = unsafe {
+                    core::ptr::read_unaligned(buf.as_ptr().add(offset) as *const libc::inotify_event)
+                };
+                offset += core::mem::size_of::<libc::inotify_event>() + event.len as usize;
+
+                if event.mask & libc::IN_IGNORED != 0 {
+                    // The watched directory was removed.
+                    self.watches.remove(&event.wd);
+                    continue;
+                }
+                wds.push(event.wd);
+            }
+        }
+
+        Ok(wds)
+    }
+}
+
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+fn parent(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{os::unix::fs::symlink, sync::mpsc};
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "signal-handler-watch-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // The number of `on_change` calls after the change, waiting for the first one.
+    fn changes<F>(path: &Path, change: F) -> usize
+    where
+        F: FnOnce(),
+    {
+        let (tx, rx) = mpsc::channel();
+        let _watcher = FileWatcher::new([path], move || tx.send(()).is_ok()).unwrap();
+
+        change();
+
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(_) => {
+                // A few poll intervals, for the events following the first batch.
+                thread::sleep(POLL_INTERVAL * 3);
+                1 + rx.try_iter().count()
+            }
+            Err(_) => 0,
+        }
+    }
+
+    #[test]
+    fn test_in_place_edit() {
+        let dir = dir("edit");
+        let path = dir.join("config");
+        fs::write(&path, "1").unwrap();
+
+        assert_eq!(changes(&path, || fs::write(&path, "22").unwrap()), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rename() {
+        let dir = dir("rename");
+        let path = dir.join("config");
+        fs::write(&path, "1").unwrap();
+
+        let change = || {
+            let tmp_path = dir.join("config.tmp");
+            fs::write(&tmp_path, "22").unwrap();
+            fs::rename(&tmp_path, &path).unwrap();
+        };
+        assert_eq!(changes(&path, change), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Like a Kubernetes ConfigMap volume, `config -> ..data/config` and `..data -> ..v1`.
+    #[test]
+    fn test_symlink_swap() {
+        let dir = dir("symlink");
+        fs::create_dir(dir.join("..v1")).unwrap();
+        fs::write(dir.join("..v1").join("config"), "1").unwrap();
+        symlink("..v1", dir.join("..data")).unwrap();
+        let path = dir.join("config");
+        symlink(Path::new("..data").join("config"), &path).unwrap();
+
+        let change = || {
+            fs::create_dir(dir.join("..v2")).unwrap();
+            fs::write(dir.join("..v2").join("config"), "22").unwrap();
+            symlink("..v2", dir.join("..data_tmp")).unwrap();
+            fs::rename(dir.join("..data_tmp"), dir.join("..data")).unwrap();
+            fs::remove_dir_all(dir.join("..v1")).unwrap();
+        };
+        assert_eq!(changes(&path, change), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "22");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,526 @@
+use core::{
+    fmt::Write as _,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Error as IoError, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use super::StatsFormat;
+
+//
+#[derive(Debug, Clone, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    pub fn inc_by(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+//
+// Stores the bits of an f64.
+#[derive(Debug, Clone, Default)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, value: f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+
+    pub fn inc(&self) {
+        self.add(1.0);
+    }
+
+    pub fn dec(&self) {
+        self.add(-1.0);
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+//
+#[derive(Debug, Clone)]
+pub struct Histogram(Arc<Mutex<HistogramState>>);
+
+#[derive(Debug)]
+struct HistogramState {
+    // Upper bounds, sorted. The +Inf bucket is implicit.
+    bounds: Vec<f64>,
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub const DEFAULT_BUCKETS: &'static [f64] = &[
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+    ];
+
+    pub fn new(buckets: &[f64]) -> Self {
+        let mut bounds = buckets
+            .iter()
+            .copied()
+            .filter(|x| x.is_finite())
+            .collect::<Vec<_>>();
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+
+        Self(Arc::new(Mutex::new(HistogramState {
+            counts: vec![0; bounds.len()],
+            bounds,
+            sum: 0.0,
+            count: 0,
+        })))
+    }
+
+    pub fn observe(&self, value: f64) {
+        let mut state = self.0.lock().expect("lock poisoned");
+
+        if let Some(i) = state.bounds.iter().position(|x| value <= *x) {
+            state.counts[i] += 1;
+        }
+        state.sum += value;
+        state.count += 1;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.0.lock().expect("lock poisoned").count
+    }
+
+    pub fn sum(&self) -> f64 {
+        self.0.lock().expect("lock poisoned").sum
+    }
+
+    // Cumulative buckets, the last one is the +Inf bucket, along with the sum and the count.
+    fn snapshot(&self) -> (Vec<(f64, u64)>, f64, u64) {
+        let state = self.0.lock().expect("lock poisoned");
+
+        let mut cumulative = 0;
+        let mut buckets = state
+            .bounds
+            .iter()
+            .zip(state.counts.iter())
+            .map(|(bound, n)| {
+                cumulative += n;
+                (*bound, cumulative)
+            })
+            .collect::<Vec<_>>();
+        buckets.push((f64::INFINITY, state.count));
+
+        (buckets, state.sum, state.count)
+    }
+}
+
+//
+#[derive(Debug, Clone)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+    Histogram(Histogram),
+}
+
+impl Metric {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Self::Counter(_) => "counter",
+            Self::Gauge(_) => "gauge",
+            Self::Histogram(_) => "histogram",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    // As registered, the key is the sanitized name.
+    name: String,
+    help: String,
+    metric: Metric,
+}
+
+//
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MetricsError {
+    // The name is registered as a metric of another type.
+    TypeConflict {
+        name: String,
+        registered: &'static str,
+    },
+    // The name is sanitized to the same metric name as a registered one, e.g. `a.b` and `a_b`.
+    NameCollision {
+        name: String,
+        registered: String,
+    },
+}
+
+impl core::fmt::Display for MetricsError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::error::Error for MetricsError {}
+
+//
+// Cloned into the components, the metrics are rendered sorted by name.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsRegistry {
+    map: Arc<Mutex<BTreeMap<String, Entry>>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registering a name again returns the registered metric, if it is of the same type.
+    pub fn counter(&self, name: &str, help: &str) -> Result<Counter, MetricsError> {
+        match self.get_or_insert(name, help, || Metric::Counter(Counter::default()))? {
+            Metric::Counter(x) => Ok(x),
+            metric => Err(type_conflict(name, &metric)),
+        }
+    }
+
+    pub fn gauge(&self, name: &str, help: &str) -> Result<Gauge, MetricsError> {
+        match self.get_or_insert(name, help, || Metric::Gauge(Gauge::default()))? {
+            Metric::Gauge(x) => Ok(x),
+            metric => Err(type_conflict(name, &metric)),
+        }
+    }
+
+    pub fn histogram(
+        &self,
+        name: &str,
+        help: &str,
+        buckets: &[f64],
+    ) -> Result<Histogram, MetricsError> {
+        match self.get_or_insert(name, help, || Metric::Histogram(Histogram::new(buckets)))? {
+            Metric::Histogram(x) => Ok(x),
+            metric => Err(type_conflict(name, &metric)),
+        }
+    }
+
+    // Only the metric registered under that exact name.
+    pub fn unregister(&self, name: &str) -> bool {
+        let mut map = self.map.lock().expect("lock poisoned");
+
+        let key = sanitize_name(name);
+        match map.get(&key) {
+            Some(entry) if entry.name == name => map.remove(&key).is_some(),
+            _ => false,
+        }
+    }
+
+    fn get_or_insert(
+        &self,
+        name: &str,
+        help: &str,
+        f: impl FnOnce() -> Metric,
+    ) -> Result<Metric, MetricsError> {
+        let mut map = self.map.lock().expect("lock poisoned");
+
+        let entry = map.entry(sanitize_name(name)).or_insert_with(|| Entry {
+            name: name.to_owned(),
+            help: help.to_owned(),
+            metric: f(),
+        });
+        if entry.name != name {
+            return Err(MetricsError::NameCollision {
+                name: name.to_owned(),
+                registered: entry.name.clone(),
+            });
+        }
+
+        Ok(entry.metric.clone())
+    }
+
+    //
+    pub fn render(&self, format: StatsFormat) -> String {
+        match format {
+            StatsFormat::Text | StatsFormat::Prometheus => self.to_prometheus(),
+            StatsFormat::Json => self.to_json(),
+        }
+    }
+
+    pub fn to_prometheus(&self) -> String {
+        let map = self.map.lock().expect("lock poisoned");
+        let mut s = String::new();
+
+        for (name, entry) in map.iter() {
+            if !entry.help.is_empty() {
+                let help = entry.help.replace('\\', "\\\\").replace('\n', "\\n");
+                let _ = writeln!(s, "# HELP {} {}", name, help);
+            }
+            let _ = writeln!(s, "# TYPE {} {}", name, entry.metric.type_name());
+
+            let _ = match &entry.metric {
+                Metric::Counter(x) => writeln!(s, "{} {}", name, x.get()),
+                Metric::Gauge(x) => writeln!(s, "{} {}", name, fmt_float(x.get())),
+                Metric::Histogram(x) => {
+                    let (buckets, sum, count) = x.snapshot();
+                    for (bound, n) in buckets {
+                        let le = fmt_float(bound);
+                        let _ = writeln!(s, "{}_bucket{{le=\"{}\"}} {}", name, le, n);
+                    }
+                    let _ = writeln!(s, "{}_sum {}", name, fmt_float(sum));
+                    writeln!(s, "{}_count {}", name, count)
+                }
+            };
+        }
+
+        s
+    }
+
+    // Non-finite values are `null`, JSON has no representation for them.
+    pub fn to_json(&self) -> String {
+        let map = self.map.lock().expect("lock poisoned");
+        let mut s = String::new();
+
+        s.push('{');
+        for (i, (name, entry)) in map.iter().enumerate() {
+            if i > 0 {
+                s.push(',');
+            }
+
+            let _ = match &entry.metric {
+                Metric::Counter(x) => write!(s, "\"{}\":{}", name, x.get()),
+                Metric::Gauge(x) => write!(s, "\"{}\":{}", name, fmt_json_float(x.get())),
+                Metric::Histogram(x) => {
+                    let (buckets, sum, count) = x.snapshot();
+                    let _ = write!(s, "\"{}\":{{\"buckets\":{{", name);
+                    for (j, (bound, n)) in buckets.into_iter().enumerate() {
+                        if j > 0 {
+                            s.push(',');
+                        }
+                        let _ = write!(s, "\"{}\":{}", fmt_float(bound), n);
+                    }
+                    let sum = fmt_json_float(sum);
+                    write!(s, "}},\"sum\":{},\"count\":{}}}", sum, count)
+                }
+            };
+        }
+        s.push_str("}\n");
+
+        s
+    }
+}
+
+fn type_conflict(name: &str, metric: &Metric) -> MetricsError {
+    MetricsError::TypeConflict {
+        name: name.to_owned(),
+        registered: metric.type_name(),
+    }
+}
+
+// Invalid characters of a Prometheus metric name are replaced with `_`.
+pub(crate) fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            'a'..='z' | 'A'..='Z' | '_' | ':' => c,
+            '0'..='9' if i > 0 => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn fmt_float(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".into()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.into()
+    } else {
+        value.to_string()
+    }
+}
+
+fn fmt_json_float(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".into()
+    }
+}
+
+//
+// Buffers the writes, each flush replaces the file by renaming a temporary file over it,
+// so that a reader, e.g. the textfile collector of node_exporter, never sees a partial dump.
+#[derive(Debug)]
+pub struct AtomicFile {
+    path: PathBuf,
+    buf: Vec<u8>,
+}
+
+impl AtomicFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            buf: vec![],
+        }
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        self.buf.extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        fs::write(&tmp_path, &self.buf)?;
+        fs::rename(&tmp_path, &self.path)?;
+        self.buf.clear();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> MetricsRegistry {
+        let registry = MetricsRegistry::new();
+
+        registry
+            .counter("app_requests", "Requests served")
+            .unwrap()
+            .inc_by(5);
+        registry.gauge("queue-depth", "").unwrap().set(1.5);
+
+        let histogram = registry
+            .histogram("latency_seconds", "Latency\nin seconds", &[0.1, 1.0])
+            .unwrap();
+        histogram.observe(0.05);
+        histogram.observe(0.5);
+        histogram.observe(3.0);
+
+        registry
+    }
+
+    #[test]
+    fn test_to_prometheus() {
+        assert_eq!(
+            registry().render(StatsFormat::Prometheus),
+            "\
+# HELP app_requests Requests served
+# TYPE app_requests counter
+app_requests 5
+# HELP latency_seconds Latency\\nin seconds
+# TYPE latency_seconds histogram
+latency_seconds_bucket{le=\"0.1\"} 1
+latency_seconds_bucket{le=\"1\"} 2
+latency_seconds_bucket{le=\"+Inf\"} 3
+latency_seconds_sum 3.55
+latency_seconds_count 3
+# TYPE queue_depth gauge
+queue_depth 1.5
+"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let registry = registry();
+        registry.gauge("ratio", "").unwrap().set(f64::NAN);
+
+        assert_eq!(
+            registry.render(StatsFormat::Json),
+            concat!(
+                r#"{"app_requests":5,"#,
+                r#""latency_seconds":{"buckets":{"0.1":1,"1":2,"+Inf":3},"sum":3.55,"count":3},"#,
+                r#""queue_depth":1.5,"ratio":null}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = MetricsRegistry::new();
+
+        // The same metric is returned for the same name.
+        registry.counter("a", "").unwrap().inc();
+        registry.counter("a", "").unwrap().inc();
+        assert_eq!(registry.counter("a", "").unwrap().get(), 2);
+
+        assert!(registry.unregister("a"));
+        assert!(!registry.unregister("a"));
+        assert_eq!(registry.counter("a", "").unwrap().get(), 0);
+
+        assert_eq!(sanitize_name("0-http.requests"), "__http_requests");
+    }
+
+    #[test]
+    fn test_registry_conflicts() {
+        let registry = MetricsRegistry::new();
+
+        registry.counter("a", "").unwrap().inc();
+        assert!(matches!(
+            registry.gauge("a", ""),
+            Err(MetricsError::TypeConflict { name, registered: "counter" }) if name == "a"
+        ));
+
+        registry.counter("a.b", "").unwrap().inc();
+        assert!(matches!(
+            registry.counter("a_b", ""),
+            Err(MetricsError::NameCollision { name, registered }) if name == "a_b" && registered == "a.b"
+        ));
+        assert!(!registry.unregister("a_b"));
+
+        // Neither of them is changed.
+        assert_eq!(registry.counter("a", "").unwrap().get(), 1);
+        assert_eq!(registry.counter("a.b", "").unwrap().get(), 1);
+    }
+
+    #[test]
+    fn test_atomic_file() {
+        let path = std::env::temp_dir().join(format!(
+            "signal-handler-metrics-{}.prom",
+            std::process::id()
+        ));
+
+        let mut file = AtomicFile::new(&path);
+        file.write_all(b"a 1\n").unwrap();
+        file.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a 1\n");
+
+        // Replaced, not appended to.
+        file.write_all(b"a 2\n").unwrap();
+        file.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a 2\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,327 @@
+use core::{fmt::Write as _, time::Duration};
+use std::{
+    fs,
+    io::{Error as IoError, Write},
+    process,
+    sync::Mutex,
+    time::Instant,
+};
+
+use crate::{
+    callback::{CallbackInfo, CallbackType},
+    handler::stats::HandlerStats,
+};
+
+pub mod metrics;
+
+pub use metrics::{AtomicFile, Counter, Gauge, Histogram, MetricsError, MetricsRegistry};
+
+//
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StatsFormat {
+    // One `key: value` line per field.
+    #[default]
+    Text,
+    // A single line JSON object, unavailable fields are `null`.
+    Json,
+    // The Prometheus text exposition format, unavailable fields are omitted.
+    Prometheus,
+}
+
+//
+// Fields are None where unsupported, most of them are read from procfs on Linux only.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ProcessStats {
+    pub pid: u32,
+    pub uptime: Option<Duration>,
+    pub rss_bytes: Option<u64>,
+    pub vsz_bytes: Option<u64>,
+    pub open_fds: Option<usize>,
+    pub threads: Option<usize>,
+    pub user_time: Option<Duration>,
+    pub system_time: Option<Duration>,
+}
+
+impl ProcessStats {
+    pub fn collect(since: Option<Instant>) -> Self {
+        let mut stats = Self {
+            pid: process::id(),
+            uptime: since.map(|x| x.elapsed()),
+            ..Default::default()
+        };
+
+        if let Ok(status) = fs::read_to_string("/proc/self/status") {
+            for line in status.lines() {
+                let (key, value) = match line.split_once(':') {
+                    Some(x) => x,
+                    None => continue,
+                };
+                let value = value.trim();
+
+                match key {
+                    "VmRSS" => stats.rss_bytes = parse_kb(value),
+                    "VmSize" => stats.vsz_bytes = parse_kb(value),
+                    "Threads" => stats.threads = value.parse().ok(),
+                    _ => {}
+                }
+            }
+        }
+
+        // The directory being read is itself an open fd.
+        if let Ok(dir) = fs::read_dir("/proc/self/fd") {
+            stats.open_fds = Some(dir.count().saturating_sub(1));
+        }
+
+        #[cfg(unix)]
+        {
+            let mut usage = core::mem::MaybeUninit::<libc::rusage>::zeroed();
+            if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } == 0 {
+                let usage = unsafe { usage.assume_init() };
+                stats.user_time = Some(timeval_to_duration(usage.ru_utime));
+                stats.system_time = Some(timeval_to_duration(usage.ru_stime));
+            }
+        }
+
+        stats
+    }
+
+    // Uptime since the initialized callbacks finished, or since the handler started without them.
+    pub fn collect_with_info(info: &CallbackInfo) -> Self {
+        let since = info.stats().and_then(|stats| {
+            stats
+                .event(CallbackType::Initialized)
+                .and_then(|x| x.last_finished_instant)
+                .or(stats.started_instant)
+        });
+
+        Self::collect(since)
+    }
+
+    pub fn render(&self, format: StatsFormat) -> String {
+        render_fields(&self.fields(), format)
+    }
+
+    pub fn to_text(&self) -> String {
+        self.render(StatsFormat::Text)
+    }
+
+    pub fn to_json(&self) -> String {
+        self.render(StatsFormat::Json)
+    }
+
+    fn fields(&self) -> Vec<(String, Option<Value>)> {
+        vec![
+            ("pid".into(), Some(Value::Count(self.pid as u64))),
+            ("uptime".into(), self.uptime.map(Value::Duration)),
+            ("rss".into(), self.rss_bytes.map(Value::Bytes)),
+            ("vsz".into(), self.vsz_bytes.map(Value::Bytes)),
+            (
+                "open_fds".into(),
+                self.open_fds.map(|x| Value::Count(x as u64)),
+            ),
+            (
+                "threads".into(),
+                self.threads.map(|x| Value::Count(x as u64)),
+            ),
+            ("user_time".into(), self.user_time.map(Value::Duration)),
+            ("system_time".into(), self.system_time.map(Value::Duration)),
+        ]
+    }
+}
+
+//
+#[cfg(feature = "impl_tokio_metrics")]
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct TokioRuntimeStats {
+    pub workers: usize,
+    pub alive_tasks: usize,
+    pub global_queue_depth: usize,
+    // Requires building with `--cfg tokio_unstable`.
+    pub blocking_threads: Option<usize>,
+}
+
+#[cfg(feature = "impl_tokio_metrics")]
+impl TokioRuntimeStats {
+    // None outside of a tokio runtime, e.g. with `Handler::handle`.
+    pub fn collect() -> Option<Self> {
+        let metrics = tokio::runtime::Handle::try_current().ok()?.metrics();
+
+        #[cfg(tokio_unstable)]
+        let blocking_threads = Some(metrics.num_blocking_threads());
+        #[cfg(not(tokio_unstable))]
+        let blocking_threads = None;
+
+        Some(Self {
+            workers: metrics.num_workers(),
+            alive_tasks: metrics.num_alive_tasks(),
+            global_queue_depth: metrics.global_queue_depth(),
+            blocking_threads,
+        })
+    }
+
+    // Followed by the counters of the handler if given.
+    pub fn render(&self, handler_stats: Option<&HandlerStats>, format: StatsFormat) -> String {
+        let mut fields = vec![
+            (
+                "tokio.workers".into(),
+                Some(Value::Count(self.workers as u64)),
+            ),
+            (
+                "tokio.alive_tasks".into(),
+                Some(Value::Count(self.alive_tasks as u64)),
+            ),
+            (
+                "tokio.global_queue_depth".into(),
+                Some(Value::Count(self.global_queue_depth as u64)),
+            ),
+            (
+                "tokio.blocking_threads".into(),
+                self.blocking_threads.map(|x| Value::Count(x as u64)),
+            ),
+        ];
+        if let Some(handler_stats) = handler_stats {
+            fields.extend(handler_fields(handler_stats));
+        }
+
+        render_fields(&fields, format)
+    }
+}
+
+//
+pub fn render_handler_stats(stats: &HandlerStats, format: StatsFormat) -> String {
+    render_fields(&handler_fields(stats), format)
+}
+
+fn handler_fields(stats: &HandlerStats) -> Vec<(String, Option<Value>)> {
+    let mut events = stats.events.iter().collect::<Vec<_>>();
+    events.sort_by_key(|(tp, _)| tp.name());
+
+    let mut fields = vec![("handler.uptime".into(), stats.uptime().map(Value::Duration))];
+    for (tp, x) in events {
+        let key = |name: &str| format!("handler.{}.{}", tp.name(), name);
+
+        fields.extend([
+            (key("received"), Some(Value::Count(x.received))),
+            (key("invocations"), Some(Value::Count(x.invocations))),
+            (key("coalesced"), Some(Value::Count(x.coalesced))),
+            (key("dropped"), Some(Value::Count(x.dropped))),
+            (key("errors"), Some(Value::Count(x.errors))),
+            (key("panics"), Some(Value::Count(x.panics))),
+            (key("last_duration"), x.last_duration.map(Value::Duration)),
+        ]);
+    }
+
+    fields
+}
+
+enum Value {
+    Bytes(u64),
+    Count(u64),
+    Duration(Duration),
+}
+
+fn render_fields(fields: &[(String, Option<Value>)], format: StatsFormat) -> String {
+    let mut s = String::new();
+
+    match format {
+        StatsFormat::Text => {
+            for (key, value) in fields {
+                let _ = match value {
+                    Some(Value::Bytes(n)) => writeln!(s, "{}: {} bytes", key, n),
+                    Some(Value::Count(n)) => writeln!(s, "{}: {}", key, n),
+                    Some(Value::Duration(dur)) => writeln!(s, "{}: {:?}", key, dur),
+                    None => writeln!(s, "{}: -", key),
+                };
+            }
+        }
+        StatsFormat::Json => {
+            s.push('{');
+            for (i, (key, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    s.push(',');
+                }
+                let _ = match value {
+                    Some(Value::Bytes(n)) => write!(s, "\"{}_bytes\":{}", key, n),
+                    Some(Value::Count(n)) => write!(s, "\"{}\":{}", key, n),
+                    Some(Value::Duration(dur)) => {
+                        write!(s, "\"{}_secs\":{:.6}", key, dur.as_secs_f64())
+                    }
+                    None => write!(s, "\"{}\":null", key),
+                };
+            }
+            s.push_str("}\n");
+        }
+        StatsFormat::Prometheus => {
+            for (key, value) in fields {
+                let key = metrics::sanitize_name(key);
+                let _ = match value {
+                    Some(Value::Bytes(n)) => writeln!(s, "{}_bytes {}", key, n),
+                    Some(Value::Count(n)) => writeln!(s, "{} {}", key, n),
+                    Some(Value::Duration(dur)) => {
+                        writeln!(s, "{}_seconds {}", key, dur.as_secs_f64())
+                    }
+                    None => Ok(()),
+                };
+            }
+        }
+    }
+
+    s
+}
+
+fn parse_kb(value: &str) -> Option<u64> {
+    value
+        .strip_suffix("kB")
+        .and_then(|x| x.trim().parse::<u64>().ok())
+        .map(|x| x * 1024)
+}
+
+#[cfg(unix)]
+fn timeval_to_duration(tv: libc::timeval) -> Duration {
+    Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+}
+
+//
+// Renders the stats on each print_stats signal.
+#[derive(Debug)]
+pub struct StatsPrinter<W> {
+    format: StatsFormat,
+    writer: Mutex<W>,
+}
+
+impl<W> StatsPrinter<W>
+where
+    W: Write,
+{
+    pub fn new(format: StatsFormat, writer: W) -> Self {
+        Self {
+            format,
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn print(&self, info: &CallbackInfo) -> Result<(), IoError> {
+        self.write(ProcessStats::collect_with_info(info).render(self.format))
+    }
+
+    #[cfg(feature = "impl_tokio_metrics")]
+    pub fn print_tokio_runtime(&self, info: &CallbackInfo) -> Result<(), IoError> {
+        let stats = TokioRuntimeStats::collect()
+            .ok_or_else(|| IoError::other("not called from a tokio runtime"))?;
+
+        self.write(stats.render(info.stats(), self.format))
+    }
+
+    pub fn print_metrics(&self, registry: &MetricsRegistry) -> Result<(), IoError> {
+        self.write(registry.render(self.format))
+    }
+
+    fn write(&self, s: String) -> Result<(), IoError> {
+        let mut writer = self.writer.lock().expect("lock poisoned");
+        writer.write_all(s.as_bytes())?;
+        writer.flush()
+    }
+}
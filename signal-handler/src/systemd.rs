@@ -0,0 +1,257 @@
+use core::time::Duration;
+use std::{
+    env,
+    io::{Error as IoError, ErrorKind as IoErrorKind},
+    os::unix::net::{SocketAddr, UnixDatagram},
+    path::Path,
+    process,
+    sync::Arc,
+};
+
+//
+// Sends sd_notify(3) states over the NOTIFY_SOCKET unix datagram socket.
+// Failures are returned, but the handler itself ignores them, like sd_notify does.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    socket: Arc<UnixDatagram>,
+    addr: Arc<SocketAddr>,
+}
+
+impl Notifier {
+    // None if not started by systemd with `Type=notify`.
+    pub fn from_env() -> Result<Option<Self>, IoError> {
+        match env::var_os("NOTIFY_SOCKET") {
+            Some(path) if !path.is_empty() => Self::connect(path).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    // A leading `@` is an abstract socket, Linux only.
+    pub fn connect<P>(path: P) -> Result<Self, IoError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        let addr = match path.to_str().and_then(|x| x.strip_prefix('@')) {
+            Some(name) => abstract_addr(name)?,
+            None => SocketAddr::from_pathname(path)?,
+        };
+
+        Ok(Self {
+            socket: Arc::new(UnixDatagram::unbound()?),
+            addr: Arc::new(addr),
+        })
+    }
+
+    // Newline separated `KEY=VALUE` assignments.
+    pub fn notify(&self, state: &str) -> Result<(), IoError> {
+        let n = self.socket.send_to_addr(state.as_bytes(), &self.addr)?;
+        if n != state.len() {
+            return Err(IoError::new(IoErrorKind::WriteZero, "partial notify"));
+        }
+
+        Ok(())
+    }
+
+    pub fn ready(&self) -> Result<(), IoError> {
+        self.notify("READY=1")
+    }
+
+    // With the MONOTONIC_USEC required by `Type=notify-reload`.
+    pub fn reloading(&self) -> Result<(), IoError> {
+        self.notify(&format!(
+            "RELOADING=1\nMONOTONIC_USEC={}",
+            monotonic_usec()?
+        ))
+    }
+
+    pub fn stopping(&self) -> Result<(), IoError> {
+        self.notify("STOPPING=1")
+    }
+
+    pub fn watchdog(&self) -> Result<(), IoError> {
+        self.notify("WATCHDOG=1")
+    }
+
+    // Newlines are replaced, they would start another assignment.
+    pub fn status(&self, status: &str) -> Result<(), IoError> {
+        self.notify(&format!("STATUS={}", status.replace('\n', " ")))
+    }
+}
+
+//
+pub type HealthCheck = Arc<dyn Fn() -> bool + Send + Sync + 'static>;
+
+// Pinged from the dispatch loop of the handler, a wedged loop stops the pings.
+#[derive(Clone)]
+pub struct Watchdog {
+    timeout: Duration,
+    health_check: Option<HealthCheck>,
+}
+
+impl core::fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("timeout", &self.timeout)
+            .field(
+                "health_check",
+                &self.health_check.as_ref().map(|_| "HealthCheck"),
+            )
+            .finish()
+    }
+}
+
+impl Watchdog {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            health_check: None,
+        }
+    }
+
+    // None unless `WatchdogSec=` is set, or if WATCHDOG_PID is another process.
+    pub fn from_env() -> Option<Self> {
+        if let Some(pid) = env::var("WATCHDOG_PID").ok().filter(|x| !x.is_empty()) {
+            if pid.parse::<u32>().ok() != Some(process::id()) {
+                return None;
+            }
+        }
+
+        let usec = env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+        if usec == 0 {
+            return None;
+        }
+
+        Some(Self::new(Duration::from_micros(usec)))
+    }
+
+    // Must return true before each ping, otherwise the ping is skipped.
+    pub fn health_check<F>(mut self, f: F) -> Self
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.health_check = Some(Arc::new(f));
+
+        self
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    // Half the timeout, as recommended by sd_watchdog_enabled(3).
+    pub fn interval(&self) -> Duration {
+        self.timeout / 2
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.health_check.as_ref().map(|f| f()).unwrap_or(true)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn abstract_addr(name: &str) -> Result<SocketAddr, IoError> {
+    use std::os::linux::net::SocketAddrExt as _;
+
+    SocketAddr::from_abstract_name(name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn abstract_addr(_name: &str) -> Result<SocketAddr, IoError> {
+    Err(IoError::new(
+        IoErrorKind::Unsupported,
+        "abstract sockets are Linux only",
+    ))
+}
+
+fn monotonic_usec() -> Result<u64, IoError> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) } != 0 {
+        return Err(IoError::last_os_error());
+    }
+
+    Ok(ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{fs, time::Duration};
+
+    fn recv(socket: &UnixDatagram) -> String {
+        let mut buf = [0; 256];
+        let n = socket.recv(&mut buf).unwrap();
+        String::from_utf8(buf[..n].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_notifier() {
+        let dir = env::temp_dir().join(format!("signal-handler-systemd-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notify.sock");
+        let _ = fs::remove_file(&path);
+
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let notifier = Notifier::connect(&path).unwrap();
+
+        notifier.ready().unwrap();
+        assert_eq!(recv(&socket), "READY=1");
+
+        notifier.reloading().unwrap();
+        let state = recv(&socket);
+        let usec = state
+            .strip_prefix("RELOADING=1\nMONOTONIC_USEC=")
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        assert!(usec > 0 && usec <= monotonic_usec().unwrap());
+
+        notifier.stopping().unwrap();
+        assert_eq!(recv(&socket), "STOPPING=1");
+
+        notifier.watchdog().unwrap();
+        assert_eq!(recv(&socket), "WATCHDOG=1");
+
+        notifier.status("reloading\nconfig").unwrap();
+        assert_eq!(recv(&socket), "STATUS=reloading config");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_notifier_abstract() {
+        use std::os::linux::net::SocketAddrExt as _;
+
+        let name = format!("signal-handler-systemd-{}", process::id());
+        let socket =
+            UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(&name).unwrap()).unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        let notifier = Notifier::connect(format!("@{}", name)).unwrap();
+
+        notifier.ready().unwrap();
+        assert_eq!(recv(&socket), "READY=1");
+    }
+
+    #[test]
+    fn test_watchdog() {
+        let watchdog = Watchdog::new(Duration::from_secs(10));
+        assert_eq!(watchdog.interval(), Duration::from_secs(5));
+        assert!(watchdog.is_healthy());
+
+        let watchdog = watchdog.health_check(|| false);
+        assert!(!watchdog.is_healthy());
+    }
+}